@@ -0,0 +1,235 @@
+use std::io::{self, BufRead, Read, Write};
+use std::process::{Command, Stdio};
+
+// Runs stty on the terminal of standard input, and returns what it printed.
+fn stty(args: &[&str]) -> Option<String> {
+	let out = Command::new("stty").args(args)
+	.stdin(Stdio::inherit()).stderr(Stdio::null())
+	.output().ok()?;
+	if out.status.success() {
+		String::from_utf8(out.stdout).ok()
+	} else {
+		None
+	}
+}
+
+// Has the terminal hand over keys as they are pressed, without echoing them, until dropped.
+// Ctrl-C is handed over as a key too, rather than interrupting the program.
+struct RawMode {
+	saved: String,
+}
+
+impl RawMode {
+	fn enter() -> Option<RawMode> {
+		let saved = stty(&["-g"])?.trim().to_owned();
+		stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"])?;
+		Some(RawMode {saved})
+	}
+}
+
+impl Drop for RawMode {
+	fn drop(&mut self) {
+		stty(&[&self.saved]);
+	}
+}
+
+enum Key {
+	Char(char),
+	Enter,
+	Backspace,
+	Delete,
+	Left,
+	Right,
+	Up,
+	Down,
+	Home,
+	End,
+	KillToEnd,
+	KillToStart,
+	Interrupt,
+	EndOfInput,
+	Other,
+}
+
+fn read_byte(input: &mut dyn Read) -> io::Result<Option<u8>> {
+	let mut byte = [0];
+	match input.read(&mut byte)? {
+		0 => Ok(None),
+		_ => Ok(Some(byte[0])),
+	}
+}
+
+// Reads the rest of an escape sequence, such as the `[A` sent by the up arrow.
+fn read_escape(input: &mut dyn Read) -> io::Result<Key> {
+	match read_byte(input)? {
+		Some(b'[') | Some(b'O') => (),
+		_ => return Ok(Key::Other),
+	}
+	let mut code = vec![];
+	loop {
+		match read_byte(input)? {
+			Some(x @ b'0'..=b'9') | Some(x @ b';') => code.push(x),
+			Some(x) => {
+				code.push(x);
+				break;
+			},
+			None => return Ok(Key::EndOfInput),
+		}
+	}
+	Ok(match &code[..] {
+		b"A" => Key::Up,
+		b"B" => Key::Down,
+		b"C" => Key::Right,
+		b"D" => Key::Left,
+		b"H" | b"1~" | b"7~" => Key::Home,
+		b"F" | b"4~" | b"8~" => Key::End,
+		b"3~" => Key::Delete,
+		_ => Key::Other,
+	})
+}
+
+fn read_key(input: &mut dyn Read) -> io::Result<Key> {
+	let first = match read_byte(input)? {
+		Some(x) => x,
+		None => return Ok(Key::EndOfInput),
+	};
+	Ok(match first {
+		b'\r' | b'\n' => Key::Enter,
+		0x7f | 0x08 => Key::Backspace,
+		0x1b => return read_escape(input),
+		0x01 => Key::Home,
+		0x05 => Key::End,
+		0x02 => Key::Left,
+		0x06 => Key::Right,
+		0x10 => Key::Up,
+		0x0e => Key::Down,
+		0x0b => Key::KillToEnd,
+		0x15 => Key::KillToStart,
+		0x03 => Key::Interrupt,
+		0x04 => Key::EndOfInput,
+		x if x < 0x20 => Key::Other,
+		x => {
+			// the leading byte of a character tells how many more bytes it has
+			let len = match x {
+				0xc0..=0xdf => 2,
+				0xe0..=0xef => 3,
+				0xf0..=0xf7 => 4,
+				_ => 1,
+			};
+			let mut bytes = vec![x];
+			for _ in 1..len {
+				match read_byte(input)? {
+					Some(x) => bytes.push(x),
+					None => return Ok(Key::EndOfInput),
+				}
+			}
+			match String::from_utf8(bytes) {
+				Ok(s) => Key::Char(s.chars().next().unwrap_or('\u{fffd}')),
+				Err(_) => Key::Other,
+			}
+		},
+	})
+}
+
+/// Reads lines from the terminal, which keeps handing over keys as they are pressed
+/// for as long as the editor lasts, so that it only has to be set up once a session.
+pub struct Editor {
+	// None if the terminal can't be put in a mode to read keys as they are pressed
+	raw: Option<RawMode>,
+}
+
+impl Editor {
+	pub fn new() -> Editor {
+		Editor {
+			raw: RawMode::enter(),
+		}
+	}
+
+	/// Reads a line after printing the prompt, letting the arrow keys move along the line and,
+	/// up and down, through the lines of the history. Returns `None` at the end of the input.
+	/// Falls back on reading the line as it is typed if the terminal isn't reading keys.
+	pub fn read_line(&self, prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+		if self.raw.is_some() {
+			return edit_line(prompt, history);
+		}
+		print!("{}", prompt);
+		io::stdout().flush()?;
+		let mut line = String::new();
+		match io::stdin().lock().read_line(&mut line)? {
+			0 => Ok(None),
+			_ => Ok(Some(line)),
+		}
+	}
+}
+
+fn edit_line(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+	let mut stdout = io::stdout();
+	let stdin = io::stdin();
+	let mut input = stdin.lock();
+	let mut line: Vec<char> = vec![];
+	let mut at = 0;
+	// which line of the history is shown, with history.len() standing for the one being typed
+	let mut recalled = history.len();
+	let mut typed: Vec<char> = vec![];
+	loop {
+		let tail = line.len() - at;
+		print!("\r{}{}\x1b[K", prompt, line.iter().collect::<String>());
+		if tail > 0 {
+			print!("\x1b[{}D", tail);
+		}
+		stdout.flush()?;
+		match read_key(&mut input)? {
+			Key::Char(c) => {
+				line.insert(at, c);
+				at += 1;
+			},
+			Key::Enter => break,
+			Key::Backspace => if at > 0 {
+				at -= 1;
+				line.remove(at);
+			},
+			Key::Delete => if at < line.len() {
+				line.remove(at);
+			},
+			Key::Left => at = at.saturating_sub(1),
+			Key::Right => at = (at + 1).min(line.len()),
+			Key::Home => at = 0,
+			Key::End => at = line.len(),
+			key @ Key::Up | key @ Key::Down => {
+				let next = match key {
+					Key::Up if recalled > 0 => recalled - 1,
+					Key::Down if recalled < history.len() => recalled + 1,
+					_ => continue,
+				};
+				if recalled == history.len() {
+					typed = line.clone();
+				}
+				recalled = next;
+				line = match history.get(recalled) {
+					Some(x) => x.chars().collect(),
+					None => typed.clone(),
+				};
+				at = line.len();
+			},
+			Key::KillToEnd => line.truncate(at),
+			Key::KillToStart => {
+				line.drain(..at);
+				at = 0;
+			},
+			Key::Interrupt => {
+				// like a shell, ^C drops the line being typed
+				println!("^C");
+				return Ok(Some(String::new()));
+			},
+			Key::EndOfInput => if line.is_empty() {
+				println!();
+				return Ok(None);
+			} else if at < line.len() {
+				line.remove(at);
+			},
+			Key::Other => (),
+		}
+	}
+	println!();
+	Ok(Some(line.into_iter().collect()))
+}
@@ -1,67 +1,88 @@
 
 use std::fmt;
 
-use super::UNICODE_MODE;
+use ::rendering::{Notation, Render, RenderOptions, Rendered};
+use ::symbols::Symbol;
 
 #[derive(Eq, PartialEq, Copy, Clone)]
 pub enum FormulaType {
-	Letter, Negation, Conjunction, Disjunction,
+	Letter, Negation, Conjunction, Disjunction, Nand, Nor, Xor, Biconditional,
 	MDiamond, MBox, Implication, None, Top, Bottom,
 }
 
 impl FormulaType {
 	pub fn bind_strength(&self) -> u8 {
 		use self::FormulaType::*;
-		match self {
-			&None => 99,
-			&Top => 99,
-			&Bottom => 99,
-			&Letter => 99, 
-			&Negation => 3,
-			&Conjunction => 2,
-			&Disjunction => 2,
-			&MDiamond => 3,
-			&MBox => 3,
-			&Implication => 1,
+		match *self {
+			None => 99,
+			Top => 99,
+			Bottom => 99,
+			Letter => 99,
+			Negation => 8,
+			Conjunction => 7,
+			Nand => 6,
+			Disjunction => 5,
+			Nor => 4,
+			Xor => 3,
+			MDiamond => 8,
+			MBox => 8,
+			Implication => 2,
+			Biconditional => 1,
 		}
 	}
 }
 
+/// The agent a diamond or box is about, as in `[a]φ` and `<a>φ`, or `None` for the plain ◇ and □.
+/// Each agent has an accessibility relation of its own.
+pub type Agent = Option<Symbol>;
 
 #[derive(Clone, Eq, PartialEq)]
 pub enum Formula {
 	Top,
 	Bottom,
-	Letter(char),
+	Letter(Symbol),
 	Negation(Box<Formula>),
 	Conjunction(Box<Formula>, Box<Formula>),
 	Disjunction(Box<Formula>, Box<Formula>),
-	MDiamond(Box<Formula>),
+	MDiamond(Agent, Box<Formula>),
+	Biconditional(Box<Formula>, Box<Formula>),
+	Xor(Box<Formula>, Box<Formula>),
+	Nand(Box<Formula>, Box<Formula>),
+	Nor(Box<Formula>, Box<Formula>),
 	
-	//not allowed inside the sequent body
-	MBox(Box<Formula>),
+	//rewritten away by `preprocess`, should the proof be given only the operators above
+	MBox(Agent, Box<Formula>),
 	Implication(Box<Formula>, Box<Formula>),
 }
 impl Formula {
-	fn get_type(&self) -> FormulaType {
+	pub fn letter(name: &str) -> Formula {
+		Formula::Letter(Symbol::intern(name))
+	}
+
+	pub fn get_type(&self) -> FormulaType {
 		use self::Formula::*;
-		match self {
-			&Top =>					FormulaType::Top,
-			&Bottom =>				FormulaType::Bottom,
-			&Letter(_) => 			FormulaType::Letter,
-			&Negation(_) => 		FormulaType::Negation,
-			&Conjunction(_,_) => 	FormulaType::Conjunction,
-			&Disjunction(_,_) => 	FormulaType::Disjunction,
-			&MDiamond(_) => 		FormulaType::MDiamond,
-			&MBox(_) => 			FormulaType::MBox,
-			&Implication(_,_) => 	FormulaType::Implication,
+		match *self {
+			Top =>					FormulaType::Top,
+			Bottom =>				FormulaType::Bottom,
+			Letter(_) => 			FormulaType::Letter,
+			Negation(_) => 		FormulaType::Negation,
+			Conjunction(_,_) => 	FormulaType::Conjunction,
+			Disjunction(_,_) => 	FormulaType::Disjunction,
+			MDiamond(_,_) => 		FormulaType::MDiamond,
+			Biconditional(_,_) => 	FormulaType::Biconditional,
+			Xor(_,_) => 			FormulaType::Xor,
+			Nand(_,_) => 			FormulaType::Nand,
+			Nor(_,_) => 			FormulaType::Nor,
+			MBox(_,_) => 			FormulaType::MBox,
+			Implication(_,_) => 	FormulaType::Implication,
 		}
 	}
 
 	fn need_parens(my_type: FormulaType, their_type: FormulaType) -> bool {
 		use self::FormulaType::*;
 		if my_type == their_type{
-			my_type == Implication
+			// ↑ and ↓ don't associate at all
+			my_type == Implication || my_type == Biconditional || my_type == Nand || my_type == Nor
 		} else if their_type == None {
 			false
 		} else if (my_type == Conjunction && their_type == Disjunction)
@@ -72,53 +93,185 @@ impl Formula {
 		}
 	}
 
-    fn repr_ascii(&self, f: &mut String, inside_type: FormulaType) {
-    	let my_type = self.get_type();
-		let parens = Formula::need_parens(my_type, inside_type);
-		if parens {f.push('(');}
-		use Formula::*;
+	/// Adds every distinct variable occurring in this formula to `out`.
+	pub fn collect_letters(&self, out: &mut Vec<Symbol>) {
+		use self::Formula::*;
+		match *self {
+			Top | Bottom => (),
+			Letter(x) => {
+				if !out.contains(&x) {
+					out.push(x);
+				}
+			},
+			Negation(ref x) | MBox(_, ref x) | MDiamond(_, ref x) => x.collect_letters(out),
+			Conjunction(ref x, ref y) | Disjunction(ref x, ref y) | Implication(ref x, ref y)
+			| Biconditional(ref x, ref y) | Xor(ref x, ref y) | Nand(ref x, ref y) | Nor(ref x, ref y) => {
+				x.collect_letters(out);
+				y.collect_letters(out);
+			},
+		}
+	}
+
+	/// Adds every distinct subformula of the form ◇φ or □φ to `out`, this formula included.
+	pub fn collect_modal(&self, out: &mut Vec<Formula>) {
+		use self::Formula::*;
+		match *self {
+			Top | Bottom | Letter(_) => (),
+			Negation(ref x) => x.collect_modal(out),
+			MDiamond(_, ref x) | MBox(_, ref x) => {
+				if !out.contains(self) {
+					out.push(self.clone());
+				}
+				x.collect_modal(out);
+			},
+			Conjunction(ref x, ref y) | Disjunction(ref x, ref y) | Implication(ref x, ref y)
+			| Biconditional(ref x, ref y) | Xor(ref x, ref y) | Nand(ref x, ref y) | Nor(ref x, ref y) => {
+				x.collect_modal(out);
+				y.collect_modal(out);
+			},
+		}
+	}
+
+	/// Pushes every ◇φ and □φ that is not nested within a ◇ or □ of another agent onto `out`.
+	/// `within` is the agent of the ◇ or □ this formula is nested within, if any.
+	pub fn collect_modal_within(&self, within: Option<Agent>, out: &mut Vec<Formula>) {
+		use self::Formula::*;
+		match *self {
+			Top | Bottom | Letter(_) => (),
+			Negation(ref x) => x.collect_modal_within(within, out),
+			MDiamond(a, ref x) | MBox(a, ref x) => {
+				if within.is_some_and(|b| b != a) {
+					return;
+				}
+				if !out.contains(self) {
+					out.push(self.clone());
+				}
+				x.collect_modal_within(Some(a), out);
+			},
+			Conjunction(ref x, ref y) | Disjunction(ref x, ref y) | Implication(ref x, ref y)
+			| Biconditional(ref x, ref y) | Xor(ref x, ref y) | Nand(ref x, ref y) | Nor(ref x, ref y) => {
+				x.collect_modal_within(within, out);
+				y.collect_modal_within(within, out);
+			},
+		}
+	}
+
+	/// Whether `x` is this formula or occurs anywhere within it.
+	pub fn has_subformula(&self, x: &Formula) -> bool {
+		use self::Formula::*;
+		self == x || match *self {
+			Top | Bottom | Letter(_) => false,
+			Negation(ref y) | MBox(_, ref y) | MDiamond(_, ref y) => y.has_subformula(x),
+			Conjunction(ref y, ref z) | Disjunction(ref y, ref z) | Implication(ref y, ref z)
+			| Biconditional(ref y, ref z) | Xor(ref y, ref z) | Nand(ref y, ref z) | Nor(ref y, ref z) => {
+				y.has_subformula(x) || z.has_subformula(x)
+			},
+		}
+	}
+
+	/// Adds every distinct agent of the diamonds and boxes in this formula to `out`.
+	pub fn collect_agents(&self, out: &mut Vec<Symbol>) {
+		use self::Formula::*;
+		match *self {
+			Top | Bottom | Letter(_) => (),
+			Negation(ref x) => x.collect_agents(out),
+			MDiamond(a, ref x) | MBox(a, ref x) => {
+				if let Some(a) = a {
+					if !out.contains(&a) {
+						out.push(a);
+					}
+				}
+				x.collect_agents(out);
+			},
+			Conjunction(ref x, ref y) | Disjunction(ref x, ref y) | Implication(ref x, ref y)
+			| Biconditional(ref x, ref y) | Xor(ref x, ref y) | Nand(ref x, ref y) | Nor(ref x, ref y) => {
+				x.collect_agents(out);
+				y.collect_agents(out);
+			},
+		}
+	}
+
+	/// The φ of ◇φ or □φ.
+	pub fn modal_body(&self) -> Option<&Formula> {
+		match *self {
+			Formula::MDiamond(_, ref x) | Formula::MBox(_, ref x) => Some(x),
+			_ => None,
+		}
+	}
+
+	/// The agent of ◇φ or □φ, or `None` for any other formula.
+	pub fn modal_agent(&self) -> Option<Agent> {
+		match *self {
+			Formula::MDiamond(a, _) | Formula::MBox(a, _) => Some(a),
+			_ => None,
+		}
+	}
+
+	/// Replaces every variable x for which `f(x)` gives a formula by that formula.
+	pub fn substitute<F: Fn(Symbol) -> Option<Formula>>(self, f: &F) -> Formula {
+		use self::Formula::*;
+		let sub = |x: Box<Formula>| Box::new(x.substitute(f));
 		match self {
-			&Top =>							f.push('T'),
-			&Bottom =>						f.push('F'),
-			&Letter(x) => 					f.push(x),
-			&Negation(ref x) => 			{f.push('-'); x.repr_ascii(f, my_type);},
-			&Conjunction(ref x, ref y) => 	{x.repr_ascii(f, my_type); f.push('&'); y.repr_ascii(f, my_type);},
-			&Disjunction(ref x, ref y) => 	{x.repr_ascii(f, my_type); f.push('V'); y.repr_ascii(f, my_type);},
-			&MDiamond(ref x) => 			{f.push_str("<>"); x.repr_ascii(f, my_type);},
-			&MBox(ref x) => 				{f.push_str("[]"); x.repr_ascii(f, my_type);},
-			&Implication(ref x, ref y) => 	{x.repr_ascii(f, my_type); f.push_str("->"); y.repr_ascii(f, my_type);},
-		};
-		if parens {f.push(')');}
-    }
+			Top => Top,
+			Bottom => Bottom,
+			Letter(x) => f(x).unwrap_or(Letter(x)),
+			Negation(x) => Negation(sub(x)),
+			Conjunction(x, y) => Conjunction(sub(x), sub(y)),
+			Disjunction(x, y) => Disjunction(sub(x), sub(y)),
+			MDiamond(a, x) => MDiamond(a, sub(x)),
+			MBox(a, x) => MBox(a, sub(x)),
+			Implication(x, y) => Implication(sub(x), sub(y)),
+			Biconditional(x, y) => Biconditional(sub(x), sub(y)),
+			Xor(x, y) => Xor(sub(x), sub(y)),
+			Nand(x, y) => Nand(sub(x), sub(y)),
+			Nor(x, y) => Nor(sub(x), sub(y)),
+		}
+	}
 
-    fn repr_unicode(&self, f: &mut String, inside_type: FormulaType) {
-    	let my_type = self.get_type();
+	pub fn display<'a>(&'a self, opts: &'a RenderOptions) -> Rendered<'a, Formula> {
+		Rendered::new(self, opts)
+	}
+
+	fn repr(&self, f: &mut String, inside_type: FormulaType, notation: Notation) {
+		let my_type = self.get_type();
 		let parens = Formula::need_parens(my_type, inside_type);
 		if parens {f.push('(');}
 		use Formula::*;
-		match self {
-			&Top =>							f.push('T'),
-			&Bottom =>						f.push('F'),
-			&Letter(x) => 					{f.push_str(&format!("{}", x));},
-			&Negation(ref x) => 			{f.push('¬'); x.repr_unicode(f, my_type);},
-			&Conjunction(ref x, ref y) => 	{x.repr_unicode(f, my_type); f.push('∧'); y.repr_unicode(f, my_type);},
-			&Disjunction(ref x, ref y) => 	{x.repr_unicode(f, my_type); f.push('∨'); y.repr_unicode(f, my_type);},
-			&MDiamond(ref x) => 			{f.push('◇'); x.repr_unicode(f, my_type);},
-			&MBox(ref x) => 				{f.push('□'); x.repr_unicode(f, my_type);},
-			&Implication(ref x, ref y) => 	{x.repr_unicode(f, my_type); f.push('→'); y.repr_unicode(f, my_type);},
+		match *self {
+			Top =>							f.push_str(notation.top()),
+			Bottom =>						f.push_str(notation.bottom()),
+			Letter(x) => 					f.push_str(&notation.variable(x.as_str())),
+			Negation(ref x) => 			{f.push_str(notation.negation()); x.repr(f, my_type, notation);},
+			Conjunction(ref x, ref y) => 	{x.repr(f, my_type, notation); f.push_str(notation.conjunction()); y.repr(f, my_type, notation);},
+			Disjunction(ref x, ref y) => 	{x.repr(f, my_type, notation); f.push_str(notation.disjunction()); y.repr(f, my_type, notation);},
+			MDiamond(a, ref x) => 			{f.push_str(&notation.diamond(a)); x.repr(f, my_type, notation);},
+			MBox(a, ref x) => 				{f.push_str(&notation.mbox(a)); x.repr(f, my_type, notation);},
+			Implication(ref x, ref y) => 	{x.repr(f, my_type, notation); f.push_str(notation.implication()); y.repr(f, my_type, notation);},
+			Biconditional(ref x, ref y) => 	{x.repr(f, my_type, notation); f.push_str(notation.biconditional()); y.repr(f, my_type, notation);},
+			Xor(ref x, ref y) => 			{x.repr(f, my_type, notation); f.push_str(notation.xor()); y.repr(f, my_type, notation);},
+			Nand(ref x, ref y) => 			{x.repr(f, my_type, notation); f.push_str(notation.nand()); y.repr(f, my_type, notation);},
+			Nor(ref x, ref y) => 			{x.repr(f, my_type, notation); f.push_str(notation.nor()); y.repr(f, my_type, notation);},
 		};
 		if parens {f.push(')');}
-    }
+	}
 }
 
-impl fmt::Debug for Formula {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Render for Formula {
+	fn render(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result {
 		let mut s = String::new();
-		if unsafe{UNICODE_MODE} {
-    		self.repr_unicode(&mut s, FormulaType::None);
-    	} else {
-    		self.repr_ascii(&mut s, FormulaType::None);
-    	}
+		self.repr(&mut s, FormulaType::None, opts.notation);
 		write!(f, "{}", &s)
-    }
+	}
+}
+
+impl fmt::Display for Formula {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.render(f, &RenderOptions::default())
+	}
+}
+
+impl fmt::Debug for Formula {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
 }
@@ -0,0 +1,301 @@
+use std::fmt;
+
+/// A JSON value. Objects keep their members in order, so that output is deterministic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	/// Parses a single JSON value, surrounded by nothing but whitespace.
+	pub fn parse(input: &str) -> Result<Json, String> {
+		let mut p = Parser {
+			input,
+			next: 0,
+		};
+		let v = p.value()?;
+		p.skip_whitespace();
+		if p.next < input.len() {
+			return Err(p.error("expected end of input"));
+		}
+		Ok(v)
+	}
+
+	/// The member of an object with the given key, if this is an object and it has one.
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match *self {
+			Json::Object(ref members) => members.iter().find(|x| x.0 == key).map(|x| &x.1),
+			_ => None,
+		}
+	}
+
+	/// The value as a whole number of at least zero, if it is one.
+	pub fn as_u32(&self) -> Option<u32> {
+		match *self {
+			Json::Number(x) if x >= 0.0 && x <= u32::MAX as f64 && x.fract() == 0.0 => Some(x as u32),
+			_ => None,
+		}
+	}
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+	write!(f, "\"")?;
+	for c in s.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?,
+		}
+	}
+	write!(f, "\"")
+}
+
+/// Writes the value compactly, on a single line.
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(x) => write!(f, "{}", x),
+			Json::Number(x) => write!(f, "{}", x),
+			Json::String(ref x) => write_string(f, x),
+			Json::Array(ref v) => {
+				write!(f, "[")?;
+				for (i, x) in v.iter().enumerate() {
+					if i > 0 {write!(f, ",")?;}
+					write!(f, "{}", x)?;
+				}
+				write!(f, "]")
+			},
+			Json::Object(ref members) => {
+				write!(f, "{{")?;
+				for (i, (k, x)) in members.iter().enumerate() {
+					if i > 0 {write!(f, ",")?;}
+					write_string(f, k)?;
+					write!(f, ":{}", x)?;
+				}
+				write!(f, "}}")
+			},
+		}
+	}
+}
+
+struct Parser<'a> {
+	input: &'a str,
+	// byte offset of the next character
+	next: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error(&self, expectation: &str) -> String {
+		match self.peek() {
+			Some(c) => format!("{} at byte {}, found '{}'", expectation, self.next, c),
+			None => format!("{} at end of input", expectation),
+		}
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.input[self.next..].chars().next()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(c) = self.peek() {
+			if !c.is_whitespace() {
+				return;
+			}
+			self.next += c.len_utf8();
+		}
+	}
+
+	fn eat(&mut self, s: &str) -> bool {
+		self.skip_whitespace();
+		if self.input[self.next..].starts_with(s) {
+			self.next += s.len();
+			true
+		} else {
+			false
+		}
+	}
+
+	fn value(&mut self) -> Result<Json, String> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some('{') => self.object(),
+			Some('[') => self.array(),
+			Some('"') => self.string().map(Json::String),
+			Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+			_ => {
+				if self.eat("null") {
+					Ok(Json::Null)
+				} else if self.eat("true") {
+					Ok(Json::Bool(true))
+				} else if self.eat("false") {
+					Ok(Json::Bool(false))
+				} else {
+					Err(self.error("expected a value"))
+				}
+			},
+		}
+	}
+
+	fn object(&mut self) -> Result<Json, String> {
+		self.eat("{");
+		let mut members = vec![];
+		if self.eat("}") {
+			return Ok(Json::Object(members));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some('"') {
+				return Err(self.error("expected a key"));
+			}
+			let k = self.string()?;
+			if !self.eat(":") {
+				return Err(self.error("expected ':'"));
+			}
+			members.push((k, self.value()?));
+			if self.eat("}") {
+				return Ok(Json::Object(members));
+			}
+			if !self.eat(",") {
+				return Err(self.error("expected ',' or '}'"));
+			}
+		}
+	}
+
+	fn array(&mut self) -> Result<Json, String> {
+		self.eat("[");
+		let mut v = vec![];
+		if self.eat("]") {
+			return Ok(Json::Array(v));
+		}
+		loop {
+			v.push(self.value()?);
+			if self.eat("]") {
+				return Ok(Json::Array(v));
+			}
+			if !self.eat(",") {
+				return Err(self.error("expected ',' or ']'"));
+			}
+		}
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.next += 1;
+		let mut s = String::new();
+		loop {
+			let c = match self.peek() {
+				Some(c) => c,
+				None => return Err(self.error("unterminated string")),
+			};
+			self.next += c.len_utf8();
+			match c {
+				'"' => return Ok(s),
+				'\\' => {
+					let e = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+					self.next += e.len_utf8();
+					s.push(match e {
+						'"' => '"',
+						'\\' => '\\',
+						'/' => '/',
+						'b' => '\u{8}',
+						'f' => '\u{c}',
+						'n' => '\n',
+						'r' => '\r',
+						't' => '\t',
+						'u' => {
+							let hex = self.input.get(self.next..self.next+4).unwrap_or("");
+							let code = u32::from_str_radix(hex, 16).map_err(|_| self.error("expected four hex digits"))?;
+							self.next += 4;
+							// surrogate pairs aren't needed for anything this crate reads
+							::std::char::from_u32(code).ok_or_else(|| self.error("unsupported escape"))?
+						},
+						_ => return Err(self.error("unknown escape")),
+					});
+				},
+				c => s.push(c),
+			}
+		}
+	}
+
+	fn number(&mut self) -> Result<Json, String> {
+		let start = self.next;
+		while let Some(c) = self.peek() {
+			if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+				break;
+			}
+			self.next += 1;
+		}
+		self.input[start..self.next].parse::<f64>()
+		.map(Json::Number)
+		.map_err(|_| format!("malformed number at byte {}", start))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn values() {
+		assert_eq!(Json::parse(" null "), Ok(Json::Null));
+		assert_eq!(Json::parse("true"), Ok(Json::Bool(true)));
+		assert_eq!(Json::parse("-1.5e2"), Ok(Json::Number(-150.0)));
+		assert_eq!(Json::parse("[]"), Ok(Json::Array(vec![])));
+		assert_eq!(Json::parse("{}"), Ok(Json::Object(vec![])));
+	}
+
+	#[test]
+	fn objects_keep_their_order() {
+		let v = Json::parse(r#"{"b": [1, 2], "a": {"c": false}}"#).unwrap();
+		assert_eq!(v, Json::Object(vec![
+			("b".to_owned(), Json::Array(vec![Json::Number(1.0), Json::Number(2.0)])),
+			("a".to_owned(), Json::Object(vec![("c".to_owned(), Json::Bool(false))])),
+		]));
+		assert_eq!(v.get("a").and_then(|x| x.get("c")), Some(&Json::Bool(false)));
+		assert_eq!(v.get("c"), None);
+	}
+
+	#[test]
+	fn string_escapes() {
+		let v = Json::parse(r#""a\"b\\c\/\n\t◇""#).unwrap();
+		assert_eq!(v, Json::String("a\"b\\c/\n\t◇".to_owned()));
+	}
+
+	#[test]
+	fn display_reads_back() {
+		let text = r#"{"s":"\"\\\n\u0001◇","n":[0,2.5,-3],"o":{"x":null,"y":true}}"#;
+		let v = Json::parse(text).unwrap();
+		assert_eq!(v.to_string(), text);
+		assert_eq!(Json::parse(&v.to_string()), Ok(v));
+	}
+
+	#[test]
+	fn whole_numbers() {
+		assert_eq!(Json::Number(3.0).as_u32(), Some(3));
+		assert_eq!(Json::Number(3.5).as_u32(), None);
+		assert_eq!(Json::Number(-1.0).as_u32(), None);
+		assert_eq!(Json::String("3".to_owned()).as_u32(), None);
+	}
+
+	#[test]
+	fn errors() {
+		assert_eq!(Json::parse("[1 2]"), Err("expected ',' or ']' at byte 3, found '2'".to_owned()));
+		assert_eq!(Json::parse("{1: 2}"), Err("expected a key at byte 1, found '1'".to_owned()));
+		assert_eq!(Json::parse(r#"{"a" 2}"#), Err("expected ':' at byte 5, found '2'".to_owned()));
+		assert_eq!(Json::parse(r#""abc"#), Err("unterminated string at end of input".to_owned()));
+		assert_eq!(Json::parse(r#""\q""#), Err("unknown escape at byte 3, found '\"'".to_owned()));
+		assert_eq!(Json::parse("1 1"), Err("expected end of input at byte 2, found '1'".to_owned()));
+		assert_eq!(Json::parse("nul"), Err("expected a value at byte 0, found 'n'".to_owned()));
+		assert_eq!(Json::parse("1..2"), Err("malformed number at byte 0".to_owned()));
+		assert_eq!(Json::parse(""), Err("expected a value at end of input".to_owned()));
+	}
+}
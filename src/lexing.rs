@@ -0,0 +1,222 @@
+use ::formulae::Agent;
+use ::parsing::ParseError;
+use ::symbols::Symbol;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TokenKind {
+	Letter(Symbol),
+	Top,
+	Bottom,
+	Negation,
+	Conjunction,
+	Disjunction,
+	Implication,
+	Biconditional,
+	Xor,
+	Nand,
+	Nor,
+	MDiamond(Agent),
+	MBox(Agent),
+	SequentArrow,
+	Comma,
+	LeftParen,
+	RightParen,
+}
+
+impl TokenKind {
+	/// The unicode spelling of the token, as used in error messages.
+	pub fn symbol(&self) -> String {
+		use self::TokenKind::*;
+		match *self {
+			Letter(x) => x.as_str().to_owned(),
+			Top => "⊤".to_owned(),
+			Bottom => "⊥".to_owned(),
+			Negation => "¬".to_owned(),
+			Conjunction => "∧".to_owned(),
+			Disjunction => "∨".to_owned(),
+			Implication => "→".to_owned(),
+			Biconditional => "↔".to_owned(),
+			Xor => "⊕".to_owned(),
+			Nand => "↑".to_owned(),
+			Nor => "↓".to_owned(),
+			MDiamond(None) => "◇".to_owned(),
+			MBox(None) => "□".to_owned(),
+			MDiamond(Some(a)) => format!("<{}>", a),
+			MBox(Some(a)) => format!("[{}]", a),
+			SequentArrow => "⇒".to_owned(),
+			Comma => ",".to_owned(),
+			LeftParen => "(".to_owned(),
+			RightParen => ")".to_owned(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Token {
+	pub kind: TokenKind,
+	/// Byte range of the token within the input.
+	pub span: Range<usize>,
+}
+
+// Every accepted spelling of every fixed token. Where one spelling is a prefix
+// of another (`-` and `->`), the longer one must come first.
+const SPELLINGS: [(&str, TokenKind); 36] = [
+	("<->", TokenKind::Biconditional), ("↔", TokenKind::Biconditional),
+	("<+>", TokenKind::Xor), ("⊕", TokenKind::Xor),
+	("!/\\", TokenKind::Nand), ("!&", TokenKind::Nand), ("↑", TokenKind::Nand), ("⊼", TokenKind::Nand),
+	("!\\/", TokenKind::Nor), ("!V", TokenKind::Nor), ("↓", TokenKind::Nor), ("⊽", TokenKind::Nor),
+	("->", TokenKind::Implication), ("→", TokenKind::Implication),
+	("=>", TokenKind::SequentArrow), ("⇒", TokenKind::SequentArrow),
+	("-", TokenKind::Negation), ("~", TokenKind::Negation), ("¬", TokenKind::Negation),
+	("/\\", TokenKind::Conjunction), ("&", TokenKind::Conjunction), ("∧", TokenKind::Conjunction),
+	("\\/", TokenKind::Disjunction), ("V", TokenKind::Disjunction), ("∨", TokenKind::Disjunction),
+	("<>", TokenKind::MDiamond(None)), ("◇", TokenKind::MDiamond(None)),
+	("[]", TokenKind::MBox(None)), ("□", TokenKind::MBox(None)),
+	("T", TokenKind::Top), ("⊤", TokenKind::Top),
+	("F", TokenKind::Bottom), ("⊥", TokenKind::Bottom),
+	("(", TokenKind::LeftParen), (")", TokenKind::RightParen), (",", TokenKind::Comma),
+];
+
+/// Splits the input into tokens, accepting both the ascii and unicode spelling of every operator.
+/// Variables are a lowercase letter followed by any number of lowercase letters, digits and underscores.
+/// The box and diamond of an agent are its name, spelled like a variable, in brackets: `[a]` and `<a>`.
+/// Whitespace separates tokens but is otherwise ignored.
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+	let mut tokens = vec![];
+	let mut i = 0;
+	while let Some(c) = input[i..].chars().next() {
+		if c.is_whitespace() {
+			i += c.len_utf8();
+			continue;
+		}
+		if let Some(&(spelling, kind)) = SPELLINGS.iter().find(|x| input[i..].starts_with(x.0)) {
+			tokens.push(Token {
+				kind,
+				span: i..i+spelling.len(),
+			});
+			i += spelling.len();
+			continue;
+		}
+		if let Some((kind, len)) = agent_modality(&input[i..]) {
+			tokens.push(Token {
+				kind,
+				span: i..i+len,
+			});
+			i += len;
+			continue;
+		}
+		let span = i..i+c.len_utf8();
+		if c.is_lowercase() {
+			let len = input[i..].find(|x| !Symbol::is_continuation(x)).unwrap_or(input.len() - i);
+			tokens.push(Token {
+				kind: TokenKind::Letter(Symbol::intern(&input[i..i+len])),
+				span: i..i+len,
+			});
+			i += len;
+		} else {
+			return Err(ParseError {
+				token: Some(c.to_string()),
+				span,
+				expectation: if c.is_uppercase() {
+					"uppercase letter not allowed".to_owned()
+				} else if c == '[' || c == '<' {
+					"expected a box or diamond, such as [a] or <a> for an agent a".to_owned()
+				} else {
+					"unrecognised character".to_owned()
+				},
+			});
+		}
+	}
+	Ok(tokens)
+}
+
+// Reads `[a]` or `<a>` at the start of the input, along with its length.
+fn agent_modality(input: &str) -> Option<(TokenKind, usize)> {
+	let (kind, close): (fn(Agent) -> TokenKind, char) = match input.chars().next() {
+		Some('[') => (TokenKind::MBox, ']'),
+		Some('<') => (TokenKind::MDiamond, '>'),
+		_ => return None,
+	};
+	let end = input.find(close)?;
+	let name = &input[1..end];
+	if Symbol::is_valid_name(name) {
+		Some((kind(Some(Symbol::intern(name))), end + 1))
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn kinds(input: &str) -> Vec<TokenKind> {
+		tokenize(input).unwrap().into_iter().map(|t| t.kind).collect()
+	}
+
+	#[test]
+	fn ascii_and_unicode_spellings_agree() {
+		use super::TokenKind::*;
+		let expected = vec![Negation, Conjunction, Disjunction, Implication, MDiamond(None), MBox(None), Top, Bottom, SequentArrow, LeftParen, RightParen];
+		assert_eq!(kinds("- & V -> <> [] T F => ( )"), expected);
+		assert_eq!(kinds("~ /\\ \\/ -> <> [] T F => ( )"), expected);
+		assert_eq!(kinds("¬ ∧ ∨ → ◇ □ ⊤ ⊥ ⇒ ( )"), expected);
+	}
+
+	#[test]
+	fn connectives_have_ascii_and_unicode_spellings() {
+		use super::TokenKind::*;
+		assert_eq!(kinds("<-> ↔ <+> ⊕"), vec![Biconditional, Biconditional, Xor, Xor]);
+		assert_eq!(kinds("!& !/\\ ↑ ⊼"), vec![Nand; 4]);
+		assert_eq!(kinds("!V !\\/ ↓ ⊽"), vec![Nor; 4]);
+	}
+
+	#[test]
+	fn longer_spellings_win() {
+		use super::TokenKind::*;
+		assert_eq!(kinds("--><->"), vec![Negation, Implication, Biconditional]);
+		assert_eq!(kinds("-!&!V"), vec![Negation, Nand, Nor]);
+		assert_eq!(kinds("<>[]=>"), vec![MDiamond(None), MBox(None), SequentArrow]);
+	}
+
+	#[test]
+	fn variables_take_letters_digits_and_underscores() {
+		let x = Symbol::intern("door_open2");
+		assert_eq!(kinds("door_open2&q"), vec![TokenKind::Letter(x), TokenKind::Conjunction, TokenKind::Letter(Symbol::intern("q"))]);
+		// a capital V ends a variable, being the ascii ∨
+		assert_eq!(kinds("pVq").len(), 3);
+	}
+
+	#[test]
+	fn whitespace_separates_tokens() {
+		let (p, q) = (Symbol::intern("p"), Symbol::intern("q"));
+		assert_eq!(kinds(" p \tq\n"), vec![TokenKind::Letter(p), TokenKind::Letter(q)]);
+		assert_eq!(kinds("pq"), vec![TokenKind::Letter(Symbol::intern("pq"))]);
+	}
+
+	#[test]
+	fn agents_are_named_in_brackets() {
+		let a = Some(Symbol::intern("alice"));
+		assert_eq!(kinds("[alice]<alice>"), vec![TokenKind::MBox(a), TokenKind::MDiamond(a)]);
+		assert_eq!(kinds("[alice]p")[0].symbol(), "[alice]");
+	}
+
+	#[test]
+	fn spans_are_byte_ranges() {
+		let tokens = tokenize("¬p ∧ q").unwrap();
+		let spans: Vec<_> = tokens.iter().map(|t| t.span.clone()).collect();
+		assert_eq!(spans, vec![0..2, 2..3, 4..7, 8..9]);
+	}
+
+	#[test]
+	fn bad_characters_are_reported_where_they_are() {
+		let e = tokenize("p & Q").unwrap_err();
+		assert_eq!((e.span, e.expectation.as_str()), (4..5, "uppercase letter not allowed"));
+		let e = tokenize("p ∧ #").unwrap_err();
+		assert_eq!((e.span, e.expectation.as_str()), (6..7, "unrecognised character"));
+		let e = tokenize("[A]p").unwrap_err();
+		assert_eq!(e.span, 0..1);
+		assert!(e.expectation.starts_with("expected a box or diamond"));
+	}
+}
@@ -0,0 +1,264 @@
+//! A sequent calculus validity checker for 1st order propositional modal logic.
+//!
+//! The typical flow is `parse` a formula, hand it to `prove` and inspect the
+//! resulting `Outcome`: either a proof tree or a proof tree together with a
+//! counter-model.
+
+pub mod lexing;
+pub mod json;
+pub mod logics;
+pub mod parsing;
+pub mod formulae;
+pub mod sequents;
+pub mod proofs;
+pub mod models;
+pub mod rendering;
+pub mod symbols;
+
+pub use parsing::{parse, parse_sequent, ParseError};
+pub use formulae::Formula;
+pub use logics::{FrameConditions, Logic};
+pub use sequents::Sequent;
+pub use proofs::{DeadlinePassed, Proof, ProofResult};
+pub use models::{Model, ModelBuilder};
+pub use rendering::{Notation, RenderOptions};
+pub use symbols::Symbol;
+
+use std::time::Instant;
+
+
+/// Rewrites → and □ in terms of ¬, ∨ and ◇, for proofs using fewer rules.
+/// ↔, ⊕, ↑ and ↓ are kept as they are.
+pub fn preprocess(f: Formula) -> Formula {
+	use Formula::*;
+	match f {
+		//stops
+		Top => Top,
+		Bottom => Bottom,
+		Letter(x) => Letter(x),
+
+		//go deeper, untouched
+		Negation(a) => Negation(Box::new(preprocess(*a))),
+		Conjunction(a, b) => Conjunction(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+		Disjunction(a, b) => Disjunction(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+		MDiamond(i, a) => MDiamond(i, Box::new(preprocess(*a))),
+		Biconditional(a, b) => Biconditional(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+		Xor(a, b) => Xor(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+		Nand(a, b) => Nand(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+		Nor(a, b) => Nor(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
+
+		//go deeper, rewritten
+		MBox(i, a) => Negation(Box::new(MDiamond(i, Box::new(Negation(Box::new(preprocess(*a))))))),
+		Implication(a, b) => Disjunction(
+			Box::new(Negation(Box::new(preprocess(*a)))),
+			Box::new(preprocess(*b))
+		),
+	}
+}
+
+/// Rewrites a formula into a shorter one, equivalent to it in every logic, by folding away
+/// ⊤ and ⊥, double negations and operators applied to the same operand twice.
+pub fn simplify(f: Formula) -> Formula {
+	use Formula::*;
+	fn negated(a: Formula) -> Formula {
+		match a {
+			Top => Bottom,
+			Bottom => Top,
+			Negation(b) => *b,
+			a => Negation(Box::new(a)),
+		}
+	}
+	match f {
+		//stops
+		Top => Top,
+		Bottom => Bottom,
+		Letter(x) => Letter(x),
+
+		//go deeper, then fold
+		Negation(a) => negated(simplify(*a)),
+		Conjunction(a, b) => match (simplify(*a), simplify(*b)) {
+			(Top, x) | (x, Top) => x,
+			(Bottom, _) | (_, Bottom) => Bottom,
+			(x, y) => if x == y {x} else {Conjunction(Box::new(x), Box::new(y))},
+		},
+		Disjunction(a, b) => match (simplify(*a), simplify(*b)) {
+			(Bottom, x) | (x, Bottom) => x,
+			(Top, _) | (_, Top) => Top,
+			(x, y) => if x == y {x} else {Disjunction(Box::new(x), Box::new(y))},
+		},
+		Implication(a, b) => match (simplify(*a), simplify(*b)) {
+			(Top, x) => x,
+			(Bottom, _) | (_, Top) => Top,
+			(x, Bottom) => negated(x),
+			(x, y) => if x == y {Top} else {Implication(Box::new(x), Box::new(y))},
+		},
+		Biconditional(a, b) => match (simplify(*a), simplify(*b)) {
+			(Top, x) | (x, Top) => x,
+			(Bottom, x) | (x, Bottom) => negated(x),
+			(x, y) => if x == y {Top} else {Biconditional(Box::new(x), Box::new(y))},
+		},
+		Xor(a, b) => match (simplify(*a), simplify(*b)) {
+			(Bottom, x) | (x, Bottom) => x,
+			(Top, x) | (x, Top) => negated(x),
+			(x, y) => if x == y {Bottom} else {Xor(Box::new(x), Box::new(y))},
+		},
+		Nand(a, b) => match (simplify(*a), simplify(*b)) {
+			(Bottom, _) | (_, Bottom) => Top,
+			(Top, x) | (x, Top) => negated(x),
+			(x, y) => if x == y {negated(x)} else {Nand(Box::new(x), Box::new(y))},
+		},
+		Nor(a, b) => match (simplify(*a), simplify(*b)) {
+			(Top, _) | (_, Top) => Bottom,
+			(Bottom, x) | (x, Bottom) => negated(x),
+			(x, y) => if x == y {negated(x)} else {Nor(Box::new(x), Box::new(y))},
+		},
+		MDiamond(i, a) => match simplify(*a) {
+			Bottom => Bottom,
+			a => MDiamond(i, Box::new(a)),
+		},
+		MBox(i, a) => match simplify(*a) {
+			Top => Top,
+			a => MBox(i, Box::new(a)),
+		},
+	}
+}
+
+/// The result of attempting to prove a formula.
+pub enum Outcome {
+	Valid(Proof),
+	Invalid(Proof, Model),
+}
+
+impl Outcome {
+	pub fn valid(&self) -> bool {
+		match *self {
+			Outcome::Valid(_) => true,
+			Outcome::Invalid(_, _) => false,
+		}
+	}
+
+	pub fn proof(&self) -> &Proof {
+		match *self {
+			Outcome::Valid(ref p) => p,
+			Outcome::Invalid(ref p, _) => p,
+		}
+	}
+
+	/// The model falsifying the formula, or the sequent, in world `1`, if there is one.
+	pub fn counter_model(&self) -> Option<&Model> {
+		match *self {
+			Outcome::Valid(_) => None,
+			Outcome::Invalid(_, ref m) => Some(m),
+		}
+	}
+}
+
+/// Attempts to prove `f` valid in the basic modal logic K, producing a counter-model if it is not.
+pub fn prove(f: &Formula) -> Outcome {
+	prove_in(f, Logic::K)
+}
+
+/// Attempts to prove `f` valid in the given logic, producing a counter-model
+/// from the logic's class of frames if it is not.
+pub fn prove_in(f: &Formula, logic: Logic) -> Outcome {
+	prove_sequent_in(&Sequent::new(vec![], vec![f.clone()]), logic)
+}
+
+/// Like `prove_in`, but gives up if the proof isn't done by the deadline.
+pub fn prove_before(f: &Formula, logic: Logic, deadline: Instant) -> Result<Outcome, DeadlinePassed> {
+	prove_sequent_before(&Sequent::new(vec![], vec![f.clone()]), logic, deadline)
+}
+
+/// Attempts to prove the sequent valid in the given logic: in every world of its frames where
+/// all the formulae on the left hold, one on the right does too. The formulae are proved as they are,
+/// unless preprocessed with `preprocess_sequent` first. A counter-model makes all those on the left
+/// true in world `1`, and all those on the right false.
+/// Any global premises of the sequent, from `Sequent::with_global_premises`, hold in every world of the
+/// frames considered, and so in every world of the counter-model.
+pub fn prove_sequent_in(s: &Sequent, logic: Logic) -> Outcome {
+	outcome(s, logic, Proof::new(s.clone(), logic))
+}
+
+/// Like `prove_sequent_in`, but gives up if the proof isn't done by the deadline.
+pub fn prove_sequent_before(s: &Sequent, logic: Logic, deadline: Instant) -> Result<Outcome, DeadlinePassed> {
+	Ok(outcome(s, logic, Proof::new_before(s.clone(), logic, deadline)?))
+}
+
+/// The sequent with every formula on either side, and every global premise, preprocessed.
+pub fn preprocess_sequent(s: &Sequent) -> Sequent {
+	Sequent::new(
+		s.left().iter().cloned().map(preprocess).collect(),
+		s.right().iter().cloned().map(preprocess).collect(),
+	).with_global_premises(s.global_premises().iter().cloned().map(preprocess).collect())
+}
+
+fn outcome(s: &Sequent, logic: Logic, p: Proof) -> Outcome {
+	if p.valid() {
+		Outcome::Valid(p)
+	} else {
+		let mut builder = ModelBuilder::new();
+		let mut letters = vec![];
+		let mut agents = vec![];
+		for f in s.left().iter().chain(s.right()) {
+			f.collect_letters(&mut letters);
+			f.collect_agents(&mut agents);
+		}
+		for x in letters {
+			builder.add_variable(x);
+		}
+		for x in agents {
+			builder.add_agent(x);
+		}
+		let mut next_avail_world = 2;
+		build_counter_model(1, &p, &mut next_avail_world, &mut vec![1], &mut builder);
+		builder.close_under(logic.frame_conditions());
+		Outcome::Invalid(p, builder.finalize())
+	}
+}
+
+// `path` holds the worlds from world 1 up to and including `curr_world`, so that loops can refer back to them.
+fn build_counter_model(curr_world: u32, proof: &Proof, next_avail_world: &mut u32, path: &mut Vec<u32>, builder: &mut ModelBuilder) {
+	//step 1: ensure current world has needed valuations
+	for letter in proof.true_here() {
+		builder.set_true_in(curr_world, *letter);
+	}
+	use proofs::ProofResult::*;
+	match *proof.proof_result() {
+		Valid => (),
+		Invalid => (),
+		Loop(_) => (),
+		Skipped => (),
+		AnyValid(ref proofs) => {
+			for p in proofs.iter() {
+				if let Loop(depth) = *p.proof_result() {
+					builder.add_access(p.start().agent(), curr_world, path[depth]);
+					continue;
+				}
+				let wid = *next_avail_world;
+				*next_avail_world += 1;
+				builder.add_access(p.start().agent(), curr_world, wid);
+				path.push(wid);
+				build_counter_model(wid, p, next_avail_world, path, builder);
+				path.pop();
+			}
+		},
+		BothValid(ref a, ref b) => {
+			let (do_a, do_b) = if proof.valid() {
+				//need to prove both
+				(true, true)
+			} else { //proof invalid!
+				if a.valid() {
+					(false, true)
+				} else { //a invalid, so b was skipped
+					(true, false)
+				}
+			};
+			if do_a {
+				build_counter_model(curr_world, a, next_avail_world, path, builder);
+			}
+			if do_b {
+				build_counter_model(curr_world, b, next_avail_world, path, builder);
+			}
+		}
+	}
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Conditions on the accessibility relation of a Kripke frame.
+/// Each condition enables the modal rules of `Sequent::step` that account for it,
+/// and the matching closure of the counter-models.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub struct FrameConditions {
+	/// Every world accesses some world (axiom D).
+	pub serial: bool,
+	/// Every world accesses itself (axiom T).
+	pub reflexive: bool,
+	/// Every world is accessed by the worlds it accesses (axiom B).
+	pub symmetric: bool,
+	/// Every world accesses all the worlds its accessible worlds access (axiom 4).
+	pub transitive: bool,
+	/// Any two worlds accessible from the same world access each other (axiom 5).
+	pub euclidean: bool,
+}
+
+impl FrameConditions {
+	/// The conditions named by the axioms in `axioms`, each one of D, T, B, 4 and 5.
+	pub fn from_axioms(axioms: &str) -> Result<FrameConditions, String> {
+		let mut c = FrameConditions::default();
+		for a in axioms.chars() {
+			match a {
+				'D' => c.serial = true,
+				'T' => c.reflexive = true,
+				'B' => c.symmetric = true,
+				'4' => c.transitive = true,
+				'5' => c.euclidean = true,
+				_ => return Err(format!("unknown axiom '{}', expected one of D, T, B, 4, 5", a)),
+			}
+		}
+		Ok(c)
+	}
+
+	/// The same conditions, together with all the conditions they imply.
+	/// For example, a reflexive and euclidean relation is an equivalence relation.
+	pub fn closed(mut self) -> FrameConditions {
+		loop {
+			let before = self;
+			self.serial |= self.reflexive;
+			self.symmetric |= self.reflexive && self.euclidean;
+			self.transitive |= self.symmetric && self.euclidean;
+			self.euclidean |= self.symmetric && self.transitive;
+			self.reflexive |= self.serial && self.symmetric && self.transitive;
+			if self == before {
+				return self;
+			}
+		}
+	}
+}
+
+impl fmt::Display for FrameConditions {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let names = [
+			(self.serial, "serial"),
+			(self.reflexive, "reflexive"),
+			(self.symmetric, "symmetric"),
+			(self.transitive, "transitive"),
+			(self.euclidean, "euclidean"),
+		];
+		let names = names.iter().filter(|x| x.0).map(|x| x.1).collect::<Vec<_>>();
+		if names.is_empty() {
+			write!(f, "all")
+		} else {
+			write!(f, "{}", names.join(", "))
+		}
+	}
+}
+
+/// The modal logics the prover can decide. Each is characterised by a class
+/// of Kripke frames, which determines the modal rules `Sequent::step` may apply
+/// and the shape of the counter-models.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum Logic {
+	/// All frames.
+	#[default]
+	K,
+	/// Serial frames. The logic of obligation, in which □φ reads "φ is obligatory".
+	KD,
+	/// Reflexive frames.
+	T,
+	/// Symmetric frames.
+	KB,
+	/// Serial and symmetric frames.
+	KDB,
+	/// Reflexive and symmetric frames.
+	B,
+	/// Transitive frames.
+	K4,
+	/// Serial and transitive frames.
+	KD4,
+	/// Reflexive and transitive frames.
+	S4,
+	/// Transitive and euclidean frames.
+	K45,
+	/// Serial, transitive and euclidean frames. The logic of belief, in which □φ reads "φ is believed".
+	KD45,
+	/// Frames whose accessibility relation is an equivalence relation.
+	/// Every counter-model can be taken to be a single cluster, in which all worlds access each other.
+	S5,
+}
+
+impl Logic {
+	/// Every logic, from weakest to strongest.
+	pub const ALL: [Logic; 12] = [
+		Logic::K, Logic::KD, Logic::T, Logic::KB, Logic::KDB, Logic::B,
+		Logic::K4, Logic::KD4, Logic::S4, Logic::K45, Logic::KD45, Logic::S5,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Logic::K => "K",
+			Logic::KD => "KD",
+			Logic::T => "T",
+			Logic::KB => "KB",
+			Logic::KDB => "KDB",
+			Logic::B => "B",
+			Logic::K4 => "K4",
+			Logic::KD4 => "KD4",
+			Logic::S4 => "S4",
+			Logic::K45 => "K45",
+			Logic::KD45 => "KD45",
+			Logic::S5 => "S5",
+		}
+	}
+
+	/// The axioms which, added to K, give this logic.
+	fn axioms(&self) -> &'static str {
+		match *self {
+			Logic::K => "",
+			Logic::KD => "D",
+			Logic::T => "T",
+			Logic::KB => "B",
+			Logic::KDB => "DB",
+			Logic::B => "TB",
+			Logic::K4 => "4",
+			Logic::KD4 => "D4",
+			Logic::S4 => "T4",
+			Logic::K45 => "45",
+			Logic::KD45 => "D45",
+			Logic::S5 => "T5",
+		}
+	}
+
+	/// The conditions on the frames of this logic, including all those they imply.
+	pub fn frame_conditions(&self) -> FrameConditions {
+		FrameConditions::from_axioms(self.axioms()).unwrap().closed()
+	}
+
+	/// The logic of the frames satisfying the given conditions, if the prover supports it.
+	pub fn with_frame_conditions(conditions: FrameConditions) -> Option<Logic> {
+		let conditions = conditions.closed();
+		Logic::ALL.iter().find(|x| x.frame_conditions() == conditions).cloned()
+	}
+}
+
+impl fmt::Display for Logic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+/// Accepts the name of any of the logics, case-insensitively, as well as K followed by
+/// any axioms among D, T, B, 4 and 5, such as KT4 for S4 or KDB4 for S5.
+impl FromStr for Logic {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Logic, String> {
+		let upper = s.to_uppercase();
+		if let Some(&l) = Logic::ALL.iter().find(|x| x.name() == upper) {
+			return Ok(l);
+		}
+		let names = Logic::ALL.iter().map(|x| x.name()).collect::<Vec<_>>().join(", ");
+		if !upper.starts_with('K') {
+			return Err(format!("unknown logic '{}', expected one of {}", s, names));
+		}
+		let conditions = FrameConditions::from_axioms(&upper[1..])
+		.map_err(|e| format!("unknown logic '{}': {}", s, e))?;
+		Logic::with_frame_conditions(conditions).ok_or_else(|| format!(
+			"logic '{}' ({} frames) is not supported, expected one of {}", s, conditions.closed(), names,
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::parsing::parse;
+	use ::{prove_before, prove_in};
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn names() {
+		assert_eq!("s4".parse::<Logic>(), Ok(Logic::S4));
+		assert_eq!("KT4".parse::<Logic>(), Ok(Logic::S4));
+		assert_eq!("KDB4".parse::<Logic>(), Ok(Logic::S5));
+		assert_eq!("KTB5".parse::<Logic>(), Ok(Logic::S5));
+		assert_eq!("KD".parse::<Logic>(), Ok(Logic::KD));
+		assert!("S3".parse::<Logic>().unwrap_err().starts_with("unknown logic 'S3', expected one of K, KD"));
+		assert!("KB4".parse::<Logic>().unwrap_err().starts_with("logic 'KB4' (symmetric, transitive, euclidean frames) is not supported"));
+		for l in Logic::ALL.iter() {
+			assert_eq!(l.name().parse::<Logic>(), Ok(*l));
+		}
+	}
+
+	#[test]
+	fn every_logic_has_its_own_frames() {
+		for (i, a) in Logic::ALL.iter().enumerate() {
+			assert_eq!(Logic::with_frame_conditions(a.frame_conditions()), Some(*a));
+			assert!(Logic::ALL[i+1..].iter().all(|b| a.frame_conditions() != b.frame_conditions()));
+		}
+	}
+
+	const AXIOMS: [(char, &str); 5] = [
+		('D', "[]p -> <>p"),
+		('T', "[]p -> p"),
+		('B', "p -> []<>p"),
+		('4', "[]p -> [][]p"),
+		('5', "<>p -> []<>p"),
+	];
+
+	fn holds(axiom: char, c: FrameConditions) -> bool {
+		match axiom {
+			'D' => c.serial,
+			'T' => c.reflexive,
+			'B' => c.symmetric,
+			'4' => c.transitive,
+			_ => c.euclidean,
+		}
+	}
+
+	#[test]
+	fn every_logic_proves_exactly_its_axioms() {
+		for l in Logic::ALL.iter() {
+			for &(axiom, f) in AXIOMS.iter() {
+				let outcome = prove_in(&parse(f).unwrap(), *l);
+				assert_eq!(outcome.valid(), holds(axiom, l.frame_conditions()), "{} in {}", f, l);
+			}
+		}
+	}
+
+	#[test]
+	fn many_instances_of_the_axioms_prove_quickly() {
+		// the cuts symmetric and euclidean frames call for are only made while the sequent mentions them,
+		// rather than for every combination of all the diamonds and boxes
+		for l in Logic::ALL.iter() {
+			for &(axiom, f) in AXIOMS.iter().filter(|x| holds(x.0, l.frame_conditions())) {
+				let instances = (0..12).map(|i| format!("({})", f.replace('p', &format!("p{}", i)))).collect::<Vec<_>>();
+				let f = parse(&instances.join(" & ")).unwrap();
+				let outcome = prove_before(&f, *l, Instant::now() + Duration::from_secs(10));
+				assert!(outcome.is_ok_and(|x| x.valid()), "12 instances of {} in {}", axiom, l);
+			}
+		}
+	}
+}
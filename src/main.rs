@@ -1,132 +1,718 @@
+extern crate sequents;
+
 use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+use std::time::{Duration, Instant};
+
+use sequents::{parse, parse_sequent, preprocess_sequent, prove_sequent_before, prove_sequent_in, simplify, DeadlinePassed, Formula, Logic, Model, Notation, Outcome, ParseError, RenderOptions, Sequent};
+use sequents::json::Json;
+
+mod editing;
+mod repl;
+
+// The exit statuses, for scripts to tell the outcomes apart.
+const EXIT_VALID: i32 = 0;
+const EXIT_INVALID: i32 = 1;
+// the input could not be read, or asks for something that can't be done
+const EXIT_BAD_INPUT: i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+// a counter-model doesn't refute its formula, so the prover is wrong
+const EXIT_UNSOUND: i32 = 70;
+
+
+#[derive(Eq, PartialEq)]
+enum Format {
+	Text,
+	Json,
+	Latex,
+	Dot,
+}
+
+impl Format {
+	fn parse(s: &str) -> Result<Format, String> {
+		match s {
+			"text" => Ok(Format::Text),
+			"json" => Ok(Format::Json),
+			"latex" => Ok(Format::Latex),
+			"dot" => Ok(Format::Dot),
+			_ => Err(format!("unknown format '{}', expected one of text, json, latex, dot", s)),
+		}
+	}
+}
 
-mod parsing;
-mod formulae;
-mod sequents;
-mod proofs;
-mod models;
+const HELP: &str = "\
+Proves formulae of propositional modal logic valid, or finds counter-models to them.
 
-static mut UNICODE_MODE: bool = false;
+Usage:
+  sequents [prove] [options] <formula>    prove a formula, the command by default
+  sequents check --model <file> [options] <formula>
+                                          tell in which worlds of a model a formula holds
+  sequents simplify [options] <formula>   fold away constants, double negations and repeats
+  sequents convert [options] <formula>    write a formula in another notation
+  sequents repl [options]                 prove formulae as they are typed
 
-use parsing::{to_unicode, parse};
-use formulae::Formula;
-use sequents::{Sequent, StepResult};
-use proofs::Proof;
-use models::ModelBuilder;
+Options:
+  --logic <name>             the logic, such as K (the default), KD, T, S4 or S5
+  --format <format>          text (the default), json, latex or dot
+  --output-notation <name>   write formulae in ascii (the default), unicode or latex
+  --unicode                  the same as --output-notation unicode
+  --input-file <file>        read the formula from a file, or - for standard input
+  --no-proof                 leave the proof out of the output
+  --preprocess               rewrite -> and [] in terms of -, V and <> before proving
+  --minimal-model            shrink counter-models before showing them
+  --timeout <seconds>        give up on a proof that takes longer
+  --batch <file>             prove the formulae of a file, one per line, or - for standard input
+  --premise <formula>        assume the formula in the world the formula is proved in
+  --global-premise <formula> assume the formula in every world
+  --model <file>             the model to check, in text or JSON
+  --quiet                    print nothing but errors, leaving the exit status to tell the outcome
+  --help                     show this help
+  --version                  show the version
 
+Arguments after -- are part of the formula, even those starting with --.
+Exits with 0 if the formula is valid, 1 if it isn't, 2 if the input is bad and 3 if out of time.
+";
 
-pub fn preprocess(f: Formula) -> Formula {
-	use Formula::*;
-	match f {
-		//stops
-		Top => Top,
-		Bottom => Bottom,
-		Letter(x) => Letter(x),
+#[derive(Eq, PartialEq, Copy, Clone)]
+enum Command {
+	Prove,
+	Check,
+	Simplify,
+	Convert,
+	Repl,
+}
 
-		//go deeper, untouched
-		Negation(a) => Negation(Box::new(preprocess(*a))),
-		Conjunction(a, b) => Conjunction(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
-		Disjunction(a, b) => Disjunction(Box::new(preprocess(*a)), Box::new(preprocess(*b))),
-		MDiamond(a) => MDiamond(Box::new(preprocess(*a))),
+impl Command {
+	fn parse(s: &str) -> Option<Command> {
+		match s {
+			"prove" => Some(Command::Prove),
+			"check" => Some(Command::Check),
+			"simplify" => Some(Command::Simplify),
+			"convert" => Some(Command::Convert),
+			"repl" => Some(Command::Repl),
+			_ => None,
+		}
+	}
 
-		//go deeper, rewritten
-		MBox(a) => Negation(Box::new(MDiamond(Box::new(Negation(Box::new(preprocess(*a))))))),
-		Implication(a, b) => Disjunction(
-			Box::new(Negation(Box::new(preprocess(*a)))),
-			Box::new(preprocess(*b))
-		),
+	fn name(&self) -> &'static str {
+		match *self {
+			Command::Prove => "prove",
+			Command::Check => "check",
+			Command::Simplify => "simplify",
+			Command::Convert => "convert",
+			Command::Repl => "repl",
+		}
 	}
+
+	// The options that make sense with the command, besides those that do with all of them.
+	fn options(&self) -> &'static [&'static str] {
+		match *self {
+			Command::Prove => &[
+				"--logic", "--input-file", "--no-proof", "--preprocess", "--minimal-model", "--timeout", "--batch", "--quiet",
+				"--premise", "--global-premise",
+			],
+			Command::Check => &["--input-file", "--model", "--quiet"],
+			Command::Simplify | Command::Convert => &["--input-file"],
+			Command::Repl => &[
+				"--logic", "--no-proof", "--preprocess", "--minimal-model", "--timeout", "--premise", "--global-premise",
+			],
+		}
+	}
+}
+
+struct Options {
+	command: Command,
+	render: RenderOptions,
+	format: Format,
+	logic: Logic,
+	// the file to read the formula from, or `-` for standard input
+	input_file: Option<String>,
+	// the file to read a model from, with the `check` command
+	model: Option<String>,
+	// the file to read formulae from, one per line, or `-` for standard input
+	batch: Option<String>,
+	// whether to rewrite → and □ away before proving
+	preprocess: bool,
+	// whether to minimise counter-models before showing them
+	minimal_model: bool,
+	// whether to show the proofs and counter-models
+	show_proof: bool,
+	show_model: bool,
+	// whether to print nothing but errors, leaving the exit status to tell the outcome
+	quiet: bool,
+	// how long to try proving each formula for
+	timeout: Option<Duration>,
+	// the premises assumed in the world of the formula, and those assumed in every world
+	premises: Vec<Formula>,
+	global_premises: Vec<Formula>,
+	formula: String,
 }
 
+// Whether the argument is meant as an option rather than as part of the formula,
+// which may well start with - or even --, for negations.
+fn is_option(a: &str) -> bool {
+	a.len() > 2 && a.starts_with("--") && a[2..].chars().all(|c| c.is_ascii_lowercase() || c == '-')
+}
+
+// Adds an argument to the formula, apart from the ones before it, so that `p q` reads as
+// two variables missing an operator rather than as the variable `pq`.
+fn add_to_formula(formula: &mut String, a: &str) {
+	if !formula.is_empty() {
+		formula.push(' ');
+	}
+	formula.push_str(a);
+}
 
-fn input() -> Option<Formula> {
-	let mut args = String::new();
-	for a in env::args().skip(1) {
-		if a == "--unicode" {
-			unsafe {
-				UNICODE_MODE = true;
+fn premise(text: &str) -> Result<Formula, String> {
+	parse(text).map_err(|e| format!("Failed to parse the premise: {}", e.render(text)))
+}
+
+// Reads the options from the command-line arguments, the program name left out.
+fn input<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+	let mut opts = Options {
+		command: Command::Prove,
+		render: RenderOptions::default(),
+		format: Format::Text,
+		logic: Logic::default(),
+		input_file: None,
+		model: None,
+		batch: None,
+		preprocess: false,
+		minimal_model: false,
+		show_proof: true,
+		show_model: true,
+		quiet: false,
+		timeout: None,
+		premises: vec![],
+		global_premises: vec![],
+		formula: String::new(),
+	};
+	let mut it = args.peekable();
+	if let Some(command) = it.peek().and_then(|x| Command::parse(x)) {
+		opts.command = command;
+		it.next();
+	}
+	while let Some(a) = it.next() {
+		if a == "--" {
+			for x in it.by_ref() {
+				add_to_formula(&mut opts.formula, &x);
 			}
-		} else {
-			args.push_str(&a);
+			break;
+		}
+		if !is_option(&a) {
+			add_to_formula(&mut opts.formula, &a);
+			continue;
+		}
+		let mut value = |what: &str| it.next().ok_or_else(|| format!("{} needs {}", a, what));
+		match a.as_str() {
+			"--help" => {
+				print!("{}", HELP);
+				process::exit(0);
+			},
+			"--version" => {
+				println!("sequents {}", env!("CARGO_PKG_VERSION"));
+				process::exit(0);
+			},
+			"--unicode" => opts.render.notation = Notation::Unicode,
+			"--output-notation" => opts.render.notation = match value("a notation")?.as_str() {
+				"ascii" => Notation::Ascii,
+				"unicode" => Notation::Unicode,
+				"latex" => Notation::Latex,
+				x => return Err(format!("unknown notation '{}', expected one of ascii, unicode, latex", x)),
+			},
+			"--format" => opts.format = Format::parse(&value("a format name")?)?,
+			"--logic" => opts.logic = value("a logic name")?.parse()?,
+			"--input-file" => opts.input_file = Some(value("a file name, or - for standard input")?),
+			"--no-proof" => opts.show_proof = false,
+			"--preprocess" => opts.preprocess = true,
+			"--minimal-model" => opts.minimal_model = true,
+			"--timeout" => {
+				let seconds = value("a number of seconds")?;
+				match seconds.parse::<f64>() {
+					// a time too long for a Duration is as good as no limit
+					Ok(x) if x > 0.0 && x.is_finite() => opts.timeout = Duration::try_from_secs_f64(x).ok(),
+					_ => return Err(format!("--timeout needs a positive number of seconds, found '{}'", seconds)),
+				}
+			},
+			"--batch" => opts.batch = Some(value("a file name, or - for standard input")?),
+			"--model" => opts.model = Some(value("a file name")?),
+			"--premise" => opts.premises.push(premise(&value("a formula")?)?),
+			"--global-premise" => opts.global_premises.push(premise(&value("a formula")?)?),
+			"--quiet" => opts.quiet = true,
+			_ => return Err(format!(
+				"unknown option '{}', see --help for the options. A formula starting with -- goes after a lone --", a,
+			)),
 		}
+		if !["--unicode", "--output-notation", "--format"].contains(&a.as_str()) && !opts.command.options().contains(&a.as_str()) {
+			return Err(format!("the {} command takes no {} option", opts.command.name(), a));
+		}
+	}
+	if opts.command == Command::Check && opts.model.is_none() {
+		return Err("check needs a model, given with --model <file>".to_owned());
+	}
+	if !opts.show_proof && opts.format == Format::Latex {
+		return Err("--format latex writes out the proof, so can't go with --no-proof".to_owned());
+	}
+	let from = if opts.batch.is_some() {
+		Some("--batch reads the formulae from its file")
+	} else if opts.input_file.is_some() {
+		Some("--input-file reads the formula from its file")
+	} else if opts.command == Command::Repl {
+		Some("repl reads the formulae as you type them")
+	} else {
+		None
+	};
+	match from {
+		Some(from) if !opts.formula.is_empty() => return Err(format!("{}, so takes none on the command line", from)),
+		None if opts.formula.is_empty() => return Err(format!("{} needs a formula, see --help", opts.command.name())),
+		_ => (),
+	}
+	if opts.batch.is_some() && opts.input_file.is_some() {
+		return Err("--batch and --input-file both give the formulae, so can't go together".to_owned());
 	}
-	args = to_unicode(args);
-	parse(&mut args)
+	Ok(opts)
 }
 
 fn main() {
-	if let Some(y) = input() {
-		println!("Given: {:?}", &y);
-		let x = preprocess(y.clone());
-		if x != y {
-			println!("...preprocessed to: {:?}", &x);
-		}
-		drop(y);
-		let m = Sequent::new(
-			vec![],
-			vec![x],
-		);
-		println!("starting with: {:?}...", &m);
-		let p = Proof::new(m);
-		p.print(0);
-		if p.valid() {
-			println!("VALID!");
-		} else {
-			//find counterexample
-			println!("INVALID!\nCounter-example:");
-			let mut builder = ModelBuilder::new();
-			let mut next_avail_world = 2;
-			build_counter_model(1, &p, &mut next_avail_world, &mut builder);
-			println!("{:?}", builder.finalize());
+	let mut opts = match input(env::args().skip(1)) {
+		Ok(opts) => opts,
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(EXIT_BAD_INPUT);
+		},
+	};
+	if let Some(file) = opts.input_file.clone() {
+		match read_file(&file) {
+			Ok(text) => opts.formula = text.trim().to_owned(),
+			Err(e) => process::exit(report_error(&opts, &format!("Failed to read the formula from {}: {}", file, e), None)),
 		}
+	}
+	let status = match opts.command {
+		Command::Repl => return repl::run(opts),
+		Command::Prove => match opts.batch {
+			Some(ref file) => batch(&opts, file),
+			None => prove(&opts),
+		},
+		Command::Check => check(&opts, opts.model.as_ref().unwrap()),
+		Command::Simplify | Command::Convert => rewrite(&opts),
+	};
+	process::exit(status);
+}
+
+// Reads the file, or standard input if it is `-`.
+fn read_file(file: &str) -> io::Result<String> {
+	if file == "-" {
+		let mut text = String::new();
+		io::stdin().read_to_string(&mut text).map(|_| text)
 	} else {
-		println!("Failed to recognize forumla input args!");
+		fs::read_to_string(file)
 	}
 }
 
-fn build_counter_model(curr_world: u32, proof: &Proof, next_avail_world: &mut u32, builder: &mut ModelBuilder) {
-	//step 1: ensure current world has needed valuations
-	for letter in proof.true_here() {
-		builder.set_true_in(curr_world, *letter);
+fn to_json_string(f: &Formula, opts: &Options) -> Json {
+	Json::String(f.display(&opts.render).to_string())
+}
+
+// Writes out the sequent the way it was most likely given, so `⇒ φ` as just φ.
+fn show_input(s: &Sequent, opts: &Options) -> String {
+	match (s.left(), s.right()) {
+		([], [f]) => f.display(&opts.render).to_string(),
+		_ => s.display(&opts.render).to_string(),
 	}
-	use proofs::ProofResult::*;
-	match proof.proof_result() {
-		&Valid => (),
-		&Invalid => (),
-		&AnyValid(ref proofs) => {
-			for p in proofs.iter() {
-				let wid = *next_avail_world;
-				*next_avail_world += 1;
-				builder.add_access(curr_world, wid);
-				build_counter_model(wid, p, next_avail_world, builder);
-			}
-		},
-		&BothValid(ref a, ref b) => {
-			let (do_a, do_b) = if proof.valid() {
-				//need to prove both
-				(true, true)
-			} else { //proof invalid!
-				if a.valid() {
-					(false, true)
-				} else { //a invalid
-					if b.valid() {
-						(true, false)
-					} else { //b invalid
-						//do the cheapest
-						if a.min_depth() <= b.min_depth() {
-							(true, false)
-						} else {
-							(false, true)
-						}
-					}
+}
+
+// Prints the error in the chosen format, and gives the exit status for it.
+// In text, errors go to standard error, away from the output, while in JSON they are the output.
+fn report_error(opts: &Options, e: &str, parse_error: Option<&ParseError>) -> i32 {
+	if opts.format == Format::Json {
+		let mut members = vec![("error".to_owned(), Json::String(e.to_owned()))];
+		if let Some(p) = parse_error {
+			let span = vec![Json::Number(p.span.start as f64), Json::Number(p.span.end as f64)];
+			members.push(("span".to_owned(), Json::Array(span)));
+		}
+		println!("{}", Json::Object(members));
+	} else {
+		eprintln!("{}", e);
+	}
+	EXIT_BAD_INPUT
+}
+
+// The sequent with the --premise formulae added to its left.
+fn with_premises(opts: &Options, s: Sequent) -> Sequent {
+	if opts.premises.is_empty() {
+		return s;
+	}
+	let left = opts.premises.iter().chain(s.left()).cloned().collect();
+	Sequent::new(left, s.right().to_vec())
+}
+
+// Proves `s` in the chosen logic, under the global premises and within the time limit if there is one.
+fn prove_limited(opts: &Options, s: &Sequent) -> Result<Outcome, DeadlinePassed> {
+	let mut s = s.clone().with_global_premises(opts.global_premises.clone());
+	if opts.preprocess {
+		s = preprocess_sequent(&s);
+	}
+	let s = &s;
+	// a deadline too far off for an Instant is as good as none
+	match opts.timeout.and_then(|t| Instant::now().checked_add(t)) {
+		None => Ok(prove_sequent_in(s, opts.logic)),
+		Some(deadline) => prove_sequent_before(s, opts.logic, deadline),
+	}
+}
+
+// Whether the counter-model fails to refute `s`, by satisfying it in world 1
+// or by falsifying a global premise somewhere.
+fn fails_to_refute(model: &Model, s: &Sequent, global_premises: &[Formula]) -> bool {
+	model.satisfies(1, &s.as_formula()) || !global_premises.iter().all(|g| model.satisfies_everywhere(g))
+}
+
+// Reports in which worlds of the model in `file` the formula holds,
+// and exits with 0 if it holds in all of them, or 1 if not.
+fn check(opts: &Options, file: &str) -> i32 {
+	let model = match fs::read_to_string(file).map_err(|e| e.to_string()).and_then(|x| x.parse::<Model>()) {
+		Ok(m) => m,
+		Err(e) => return report_error(opts, &format!("Failed to read the model from {}: {}", file, e), None),
+	};
+	let s = match parse_sequent(&opts.formula) {
+		Ok(s) => s,
+		Err(e) => return report_error(opts, &format!("Failed to parse the formula: {}", e.render(&opts.formula)), Some(&e)),
+	};
+	if opts.format == Format::Latex {
+		return report_error(opts, "The check command has no latex format", None);
+	}
+	let y = s.as_formula();
+	let holds = (1..=model.num_worlds).map(|w| model.satisfies(w, &y)).collect::<Vec<_>>();
+	let status = if holds.iter().all(|&x| x) {EXIT_VALID} else {EXIT_INVALID};
+	if opts.quiet {
+		return status;
+	}
+	if opts.format == Format::Dot {
+		print!("{}", model.to_dot());
+	} else if opts.format == Format::Json {
+		let worlds = holds.iter().enumerate().map(|(i, &x)| Json::Object(vec![
+			("world".to_owned(), Json::Number((i + 1) as f64)),
+			("holds".to_owned(), Json::Bool(x)),
+		])).collect();
+		println!("{}", Json::Object(vec![
+			("formula".to_owned(), Json::String(show_input(&s, opts))),
+			("worlds".to_owned(), Json::Array(worlds)),
+		]));
+	} else {
+		println!("Given: {}", show_input(&s, opts));
+		for (i, &x) in holds.iter().enumerate() {
+			println!("world {}: {}", i + 1, if x {"holds"} else {"does not hold"});
+		}
+	}
+	status
+}
+
+// Proves the formulae in `file`, one per line, and prints how each fared. Anything after a `#`
+// is a comment, in which `expect: valid` or `expect: invalid` gives the verdict the formula
+// should get. With --quiet, only the lines that fail are printed, in text to standard error like
+// other errors. Exits with status 2 if any line fails to parse, 3 if any proof runs out of time
+// and 1 if any formula gets another verdict.
+fn batch(opts: &Options, file: &str) -> i32 {
+	if opts.format == Format::Latex || opts.format == Format::Dot {
+		return report_error(opts, "The --batch option only has the text and json formats", None);
+	}
+	let text = match read_file(file) {
+		Ok(text) => text,
+		Err(e) => return report_error(opts, &format!("Failed to read the formulae from {}: {}", file, e), None),
+	};
+	let (mut valid, mut invalid, mut errors, mut timeouts, mut violations) = (0, 0, 0, 0, 0);
+	// each with whether the line failed
+	let mut results = vec![];
+	let failure = |line: String| if opts.quiet {eprintln!("{}", line)} else {println!("{}", line)};
+	for (i, line) in text.lines().enumerate() {
+		let (formula, comment) = match line.find('#') {
+			Some(at) => (&line[..at], line[at+1..].trim()),
+			None => (line, ""),
+		};
+		if formula.trim().is_empty() {
+			continue;
+		}
+		let mut members = vec![("line".to_owned(), Json::Number((i + 1) as f64))];
+		let expected = match comment.strip_prefix("expect:").map(str::trim) {
+			None => None,
+			Some("valid") => Some(true),
+			Some("invalid") => Some(false),
+			Some(x) => {
+				errors += 1;
+				let e = format!("unknown expectation '{}', expected valid or invalid", x);
+				if opts.format == Format::Json {
+					members.push(("error".to_owned(), Json::String(e)));
+					results.push((Json::Object(members), true));
+				} else {
+					failure(format!("line {}: {}", i + 1, e));
 				}
-			};
-			if do_a {
-				build_counter_model(curr_world, a, next_avail_world, builder);
-			}
-			if do_b {
-				build_counter_model(curr_world, b, next_avail_world, builder);
+				continue;
+			},
+		};
+		let s = match parse_sequent(formula) {
+			Ok(s) => with_premises(opts, s),
+			Err(e) => {
+				errors += 1;
+				let column = formula[..e.span.start].chars().count() + 1;
+				if opts.format == Format::Json {
+					let span = vec![Json::Number(e.span.start as f64), Json::Number(e.span.end as f64)];
+					members.push(("error".to_owned(), Json::String(e.to_string())));
+					members.push(("span".to_owned(), Json::Array(span)));
+					results.push((Json::Object(members), true));
+				} else {
+					failure(format!("line {}: parse error at column {}: {}", i + 1, column, e));
+				}
+				continue;
+			},
+		};
+		let start = Instant::now();
+		let outcome = match prove_limited(opts, &s) {
+			Ok(outcome) => outcome,
+			Err(e) => {
+				timeouts += 1;
+				if opts.format == Format::Json {
+					members.push(("formula".to_owned(), Json::String(show_input(&s, opts))));
+					members.push(("error".to_owned(), Json::String(e.to_string())));
+					results.push((Json::Object(members), true));
+				} else {
+					failure(format!("line {}: gave up, as {}: {}", i + 1, e, show_input(&s, opts)));
+				}
+				continue;
+			},
+		};
+		let millis = start.elapsed().as_secs_f64() * 1000.0;
+		if let Some(model) = outcome.counter_model() {
+			if fails_to_refute(model, &s, &opts.global_premises) {
+				eprintln!("ERROR: line {}: the counter-example does not refute {}", i + 1, show_input(&s, opts));
+				process::exit(EXIT_UNSOUND);
 			}
 		}
+		if outcome.valid() {
+			valid += 1;
+		} else {
+			invalid += 1;
+		}
+		let verdict = if outcome.valid() {"valid"} else {"invalid"};
+		let size = outcome.proof().size();
+		let violated = expected.is_some_and(|x| x != outcome.valid());
+		if violated {
+			violations += 1;
+		}
+		if opts.format == Format::Json {
+			members.extend(vec![
+				("formula".to_owned(), Json::String(show_input(&s, opts))),
+				("valid".to_owned(), Json::Bool(outcome.valid())),
+				("expected".to_owned(), expected.map(Json::Bool).unwrap_or(Json::Null)),
+				("proof_size".to_owned(), Json::Number(size as f64)),
+				("time_ms".to_owned(), Json::Number(millis)),
+			]);
+			results.push((Json::Object(members), violated));
+		} else if violated {
+			let expected = if outcome.valid() {"invalid"} else {"valid"};
+			failure(format!("line {}: {}, expected {} ({} rules, {:.2} ms): {}", i + 1, verdict, expected, size, millis, show_input(&s, opts)));
+		} else if !opts.quiet {
+			println!("line {}: {} ({} rules, {:.2} ms): {}", i + 1, verdict, size, millis, show_input(&s, opts));
+		}
+	}
+	let failed = errors + timeouts + violations > 0;
+	if opts.format == Format::Json && (failed || !opts.quiet) {
+		// with --quiet, only the lines that failed are listed
+		let results = results.into_iter().filter(|x| x.1 || !opts.quiet).map(|x| x.0).collect();
+		println!("{}", Json::Object(vec![
+			("logic".to_owned(), Json::String(opts.logic.name().to_owned())),
+			("results".to_owned(), Json::Array(results)),
+			("valid".to_owned(), Json::Number(valid as f64)),
+			("invalid".to_owned(), Json::Number(invalid as f64)),
+			("errors".to_owned(), Json::Number(errors as f64)),
+			("timeouts".to_owned(), Json::Number(timeouts as f64)),
+			("violations".to_owned(), Json::Number(violations as f64)),
+		]));
+	} else if opts.format == Format::Text && !opts.quiet {
+		println!("{} valid, {} invalid, {} errors, {} timed out, {} expectations not met", valid, invalid, errors, timeouts, violations);
+	}
+	if errors > 0 {
+		EXIT_BAD_INPUT
+	} else if timeouts > 0 {
+		EXIT_TIMEOUT
+	} else if violations > 0 {
+		EXIT_INVALID
+	} else {
+		EXIT_VALID
+	}
+}
+
+// Prints the formula in the chosen notation, simplified first with the `simplify` command.
+fn rewrite(opts: &Options) -> i32 {
+	if opts.format != Format::Text && opts.format != Format::Json {
+		return report_error(opts, &format!("The {} command only has the text and json formats", opts.command.name()), None);
+	}
+	let y = match parse(&opts.formula) {
+		Ok(y) => y,
+		Err(e) => return report_error(opts, &format!("Failed to parse the formula: {}", e.render(&opts.formula)), Some(&e)),
+	};
+	let x = if opts.command == Command::Simplify {simplify(y.clone())} else {y.clone()};
+	if opts.format == Format::Json {
+		let mut members = vec![("formula".to_owned(), to_json_string(&y, opts))];
+		if opts.command == Command::Simplify {
+			members.push(("simplified".to_owned(), to_json_string(&x, opts)));
+		}
+		println!("{}", Json::Object(members));
+	} else {
+		println!("{}", x.display(&opts.render));
+	}
+	EXIT_VALID
+}
+
+fn prove(opts: &Options) -> i32 {
+	let args = &opts.formula;
+	match parse_sequent(args) {
+		Ok(s) => show_proof(opts, &with_premises(opts, s)),
+		Err(e) => report_error(opts, &format!("Failed to parse the formula: {}", e.render(args)), Some(&e)),
+	}
+}
+
+// Proves `y`, prints the outcome in the chosen format and gives the exit status for it.
+fn show_proof(opts: &Options, s: &Sequent) -> i32 {
+	let logic = opts.logic;
+	// the formula a counter-model is to falsify
+	let y = &s.as_formula();
+	// the sequent as proved, which `prove_limited` preprocesses likewise
+	let x = if opts.preprocess {preprocess_sequent(s)} else {s.clone()};
+	let outcome = match prove_limited(opts, s) {
+		Ok(outcome) => outcome,
+		Err(e) => {
+			report_error(opts, &format!("Gave up, as {}", e), None);
+			return EXIT_TIMEOUT;
+		},
+	};
+	let counter_model = outcome.counter_model().map(|m| if opts.minimal_model {
+		m.minimized(y, &opts.global_premises, logic.frame_conditions())
+	} else {
+		m.clone()
+	});
+	if opts.quiet {
+		// only the exit status tells the outcome
+	} else if opts.format == Format::Json {
+		let mut members = vec![
+			("logic".to_owned(), Json::String(logic.name().to_owned())),
+			("formula".to_owned(), Json::String(show_input(s, opts))),
+		];
+		if !opts.global_premises.is_empty() {
+			let global = opts.global_premises.iter().map(|g| to_json_string(g, opts)).collect();
+			members.push(("global_premises".to_owned(), Json::Array(global)));
+		}
+		members.extend(vec![
+			("preprocessed".to_owned(), Json::String(show_input(&x, opts))),
+			("valid".to_owned(), Json::Bool(outcome.valid())),
+			("proof".to_owned(), if opts.show_proof {outcome.proof().to_json(&opts.render)} else {Json::Null}),
+			("counter_model".to_owned(), counter_model.as_ref().map(|m| m.to_json()).unwrap_or(Json::Null)),
+		]);
+		println!("{}", Json::Object(members));
+	} else if opts.format == Format::Latex {
+		print!("{}", outcome.proof().to_latex());
+	} else if opts.format == Format::Dot {
+		if opts.show_proof {
+			print!("{}", outcome.proof().to_dot(&opts.render));
+		}
+		if let Some(ref model) = counter_model {
+			print!("{}", model.to_dot());
+		}
+	} else {
+		if logic != Logic::K {
+			println!("Logic: {}", logic);
+		}
+		println!("Given: {}", show_input(s, opts));
+		for g in opts.global_premises.iter() {
+			println!("Assuming everywhere: {}", g.display(&opts.render));
+		}
+		if show_input(&x, opts) != show_input(s, opts) {
+			println!("...preprocessed to: {}", show_input(&x, opts));
+		}
+		if opts.show_proof {
+			println!("starting with: {}...", x.display(&opts.render));
+			print!("{}", outcome.proof().display(&opts.render));
+		}
+		match counter_model.as_ref() {
+			None => println!("VALID!"),
+			Some(model) if opts.show_model => {
+				println!("INVALID!\nCounter-example:");
+				println!("{:?}", model);
+			},
+			Some(_) => println!("INVALID!"),
+		}
+	}
+	if let Some(model) = counter_model {
+		if fails_to_refute(&model, s, &opts.global_premises) {
+			// the prover is wrong, and the output above can't be trusted
+			eprintln!("ERROR: the counter-example does not refute {}", show_input(s, opts));
+			process::exit(EXIT_UNSOUND);
+		}
+	}
+	if outcome.valid() {EXIT_VALID} else {EXIT_INVALID}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(v: &[&str]) -> Result<Options, String> {
+		input(v.iter().map(|x| x.to_string()))
+	}
+
+	#[test]
+	fn formulae_may_take_several_arguments() {
+		assert_eq!(args(&["[]p", "->", "p"]).unwrap().formula, "[]p -> p");
+		// kept apart, so that the missing operator is reported
+		assert_eq!(args(&["p", "q"]).unwrap().formula, "p q");
+		let opts = args(&["-p", "--logic", "S4", "V", "p"]).unwrap();
+		assert_eq!((opts.formula.as_str(), opts.logic), ("-p V p", Logic::S4));
+		assert_eq!(args(&["p", "->", "--", "-q"]).unwrap().formula, "p -> -q");
+	}
+
+	#[test]
+	fn everything_after_a_lone_double_dash_is_formula() {
+		// --p would be taken for an option otherwise
+		let opts = args(&["--quiet", "--", "--p", "--logic", "S4"]).unwrap();
+		assert_eq!((opts.formula.as_str(), opts.quiet, opts.logic), ("--p --logic S4", true, Logic::K));
+		assert_eq!(args(&["--", "--p"]).unwrap().formula, "--p");
+		assert!(parse_sequent(&args(&["--", "--p"]).unwrap().formula).is_ok());
+	}
+
+	#[test]
+	fn unknown_and_misplaced_options_are_errors() {
+		let err = |v: &[&str]| args(v).err().unwrap_or_else(|| panic!("{:?} was accepted", v));
+		let hint = "see --help for the options. A formula starting with -- goes after a lone --";
+		assert_eq!(err(&["--qiuet", "p"]), format!("unknown option '--qiuet', {}", hint));
+		assert_eq!(err(&["--p"]), format!("unknown option '--p', {}", hint));
+		assert_eq!(err(&["p", "--logic"]), "--logic needs a logic name");
+		assert_eq!(err(&["check", "--model", "m.txt", "--logic", "T", "p"]), "the check command takes no --logic option");
+		assert_eq!(err(&["simplify", "--timeout", "1", "p"]), "the simplify command takes no --timeout option");
+		assert_eq!(err(&["--quiet"]), "prove needs a formula, see --help");
+		assert_eq!(err(&["repl", "p"]), "repl reads the formulae as you type them, so takes none on the command line");
+		assert_eq!(err(&["check", "p"]), "check needs a model, given with --model <file>");
+	}
+
+	#[test]
+	fn commands_come_first() {
+		assert!(args(&["simplify", "p&T"]).unwrap().command == Command::Simplify);
+		assert!(args(&["convert", "--output-notation", "unicode", "p"]).unwrap().command == Command::Convert);
+		// anywhere else, a command's name is a variable
+		let opts = args(&["p", "V", "check"]).unwrap();
+		assert!(opts.command == Command::Prove);
+		assert_eq!(opts.formula, "p V check");
+	}
+
+	#[test]
+	fn counter_models_that_fail_to_refute_are_caught() {
+		// such a counter-model makes the validator exit with EXIT_UNSOUND
+		let s = parse_sequent("<>p => []p").unwrap();
+		let refuting = Model::from_text("worlds: 3\naccess: 1 -> 2, 1 -> 3\nvalue p: 2").unwrap();
+		assert!(!fails_to_refute(&refuting, &s, &[]));
+		let satisfying = Model::from_text("worlds: 2\naccess: 1 -> 2\nvalue p: 2").unwrap();
+		assert!(fails_to_refute(&satisfying, &s, &[]));
+		// the global premises have to hold in every world too
+		assert!(fails_to_refute(&refuting, &s, &[parse("p").unwrap()]));
+		assert!(!fails_to_refute(&refuting, &s, &[parse("p -> []p").unwrap()]));
 	}
 }
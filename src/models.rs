@@ -1,35 +1,477 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
+use std::str::FromStr;
 
+use ::formulae::{Agent, Formula};
+use ::json::Json;
+use ::logics::FrameConditions;
+use ::rendering::dot_quoted;
+use ::symbols::Symbol;
+
+#[derive(Clone)]
 pub struct Model {
 	pub num_worlds: u32,
-	pub accessibility_function: HashSet<(u32, u32)>,
-	pub valuations: HashMap<char, HashSet<u32>>,
+	/// The accessibility relation of each agent, always with one for the plain ◇ and □ under `None`.
+	/// An agent without a relation of its own accesses no worlds.
+	pub accessibility_functions: BTreeMap<Agent, BTreeSet<(u32, u32)>>,
+	/// The worlds each variable is true in. A variable false everywhere may have an empty entry.
+	pub valuations: BTreeMap<Symbol, BTreeSet<u32>>,
 }
 
-impl fmt::Debug for Model {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-    	let preamble = "Model:\n  worlds: ";
-    	match self.num_worlds {
-    		1 => write!(f, "{}{{1}}\n", preamble),
-    		2 => write!(f, "{}{{1, 2}}\n", preamble),
-    		3 => write!(f, "{}{{1, 2, 3}}\n", preamble),
-    		4 => write!(f, "{}{{1, 2, 3, 4}}\n", preamble),
-    		x => write!(f, "{}{{1, 2, ... {}}}\n", preamble, x),
-    	}?;
-    	if !self.accessibility_function.is_empty() {
-    		write!(f, "  access fn: {:?}\n", &self.accessibility_function)?;
-    	}
-		if !self.valuations.is_empty() {
-			write!(f, "  value fn: {{\n")?;
-			for (k, v) in self.valuations.iter() {
-				write!(f, "    {}: {:?}", k, v)?;
-			}
-			write!(f, "\n  }}")?;
+impl Model {
+	/// Whether `f` holds in the given world, under the usual Kripke semantics:
+	/// ◇φ holds in a world if φ holds in some world it accesses, and □φ if φ holds in all of them,
+	/// where the worlds accessed are those of the agent of the ◇ or □.
+	pub fn satisfies(&self, world: u32, f: &Formula) -> bool {
+		use formulae::Formula::*;
+		match *f {
+			Top => true,
+			Bottom => false,
+			Letter(x) => self.valuations.get(&x).is_some_and(|v| v.contains(&world)),
+			Negation(ref x) => !self.satisfies(world, x),
+			Conjunction(ref x, ref y) => self.satisfies(world, x) && self.satisfies(world, y),
+			Disjunction(ref x, ref y) => self.satisfies(world, x) || self.satisfies(world, y),
+			Implication(ref x, ref y) => !self.satisfies(world, x) || self.satisfies(world, y),
+			Biconditional(ref x, ref y) => self.satisfies(world, x) == self.satisfies(world, y),
+			Xor(ref x, ref y) => self.satisfies(world, x) != self.satisfies(world, y),
+			Nand(ref x, ref y) => !(self.satisfies(world, x) && self.satisfies(world, y)),
+			Nor(ref x, ref y) => !(self.satisfies(world, x) || self.satisfies(world, y)),
+			MDiamond(a, ref x) => self.accessible_from(a, world).any(|w| self.satisfies(w, x)),
+			MBox(a, ref x) => self.accessible_from(a, world).all(|w| self.satisfies(w, x)),
+		}
+	}
+
+	/// Whether `f` holds in every world of the model.
+	pub fn satisfies_everywhere(&self, f: &Formula) -> bool {
+		(1..=self.num_worlds).all(|w| self.satisfies(w, f))
+	}
+
+	/// The model in the JSON form `Model::from_json` reads, with everything in ascending order.
+	pub fn to_json(&self) -> Json {
+		let worlds = |v: Vec<u32>| Json::Array(v.into_iter().map(|x| Json::Number(x as f64)).collect());
+		let pairs = |r: &BTreeSet<(u32, u32)>| Json::Array(r.iter().map(|&(a, b)| worlds(vec![a, b])).collect());
+		let valuations = self.valuations.iter()
+		.map(|(k, v)| (k.to_string(), worlds(v.iter().cloned().collect())))
+		.collect();
+		let mut members = vec![
+			("worlds".to_owned(), Json::Number(self.num_worlds as f64)),
+			("access".to_owned(), pairs(self.relation(None))),
+		];
+		let agents = self.agents().map(|a| (a.to_string(), pairs(self.relation(Some(a))))).collect::<Vec<_>>();
+		if !agents.is_empty() {
+			members.push(("agents".to_owned(), Json::Object(agents)));
+		}
+		members.push(("valuations".to_owned(), Json::Object(valuations)));
+		Json::Object(members)
+	}
+
+	/// The model as a directed graph in Graphviz's DOT language, with a node for every world
+	/// labelled with the variables true there, and an edge for every access, labelled with its agent if it has one.
+	pub fn to_dot(&self) -> String {
+		let mut out = "digraph model {\n\tnode [shape=circle];\n".to_owned();
+		for w in 1..=self.num_worlds {
+			let mut label = w.to_string();
+			for x in self.true_in(w) {
+				label.push_str(&format!("\n{}", x));
+			}
+			out.push_str(&format!("\t{} [label={}];\n", w, dot_quoted(&label)));
+		}
+		for (agent, r) in self.accessibility_functions.iter() {
+			for &(a, b) in r.iter() {
+				match *agent {
+					Some(x) => out.push_str(&format!("\t{} -> {} [label={}];\n", a, b, dot_quoted(x.as_str()))),
+					None => out.push_str(&format!("\t{} -> {};\n", a, b)),
+				}
+			}
+		}
+		out.push_str("}\n");
+		out
+	}
+
+	/// Reads a model from its JSON form, an object like
+	/// `{"worlds": 2, "access": [[1, 2]], "valuations": {"p": [2]}}`.
+	/// Instead of their number, the worlds can also be given as a list.
+	/// The relations of any agents go in an object like `"agents": {"a": [[1, 1], [2, 2]]}`.
+	pub fn from_json(json: &Json) -> Result<Model, String> {
+		fn worlds(v: &Json, what: &str) -> Result<Vec<u32>, String> {
+			match *v {
+				Json::Array(ref v) => v.iter()
+				.map(|x| x.as_u32().ok_or_else(|| format!("{} should be a list of worlds, found {}", what, x)))
+				.collect(),
+				_ => Err(format!("{} should be a list of worlds, found {}", what, v)),
+			}
+		}
+		if let Json::Object(ref members) = *json {
+			if let Some(k) = members.iter().map(|x| &x.0).find(|k| !["worlds", "access", "agents", "valuations"].contains(&k.as_str())) {
+				return Err(format!("unknown key \"{}\", expected one of \"worlds\", \"access\", \"agents\", \"valuations\"", k));
+			}
+		} else {
+			return Err(format!("a model should be an object, found {}", json));
+		}
+		let mut r = RawModel::default();
+		match json.get("worlds") {
+			None => (),
+			Some(x @ &Json::Number(_)) => r.num_worlds = Some(x.as_u32().ok_or("\"worlds\" should be a number of worlds")?),
+			Some(x) => r.num_worlds = worlds(x, "\"worlds\"")?.into_iter().max(),
+		}
+		fn pairs(x: &Json, what: &str) -> Result<Vec<(u32, u32)>, String> {
+			let pairs = match *x {
+				Json::Array(ref v) => v,
+				_ => return Err(format!("{} should be a list of pairs of worlds, found {}", what, x)),
+			};
+			pairs.iter().map(|p| match worlds(p, &format!("each pair in {}", what))?[..] {
+				[a, b] => Ok((a, b)),
+				_ => Err(format!("each pair in {} should have two worlds, found {}", what, p)),
+			}).collect()
+		}
+		if let Some(x) = json.get("access") {
+			r.access(None, pairs(x, "\"access\"")?);
+		}
+		match json.get("agents") {
+			None => (),
+			Some(Json::Object(members)) => {
+				for (k, v) in members.iter() {
+					let agent = r.agent(k)?;
+					r.access(Some(agent), pairs(v, &format!("the access of {}", k))?);
+				}
+			},
+			Some(x) => return Err(format!("\"agents\" should be an object, found {}", x)),
+		}
+		match json.get("valuations") {
+			None => (),
+			Some(Json::Object(members)) => {
+				for (k, v) in members.iter() {
+					r.valuation(k, worlds(v, &format!("the valuation of {}", k))?)?;
+				}
+			},
+			Some(x) => return Err(format!("\"valuations\" should be an object, found {}", x)),
+		}
+		r.finalize()
+	}
+
+	/// Reads a model from its textual form, with one line for the worlds, one for the access,
+	/// one for the access of each agent and one for the valuation of each variable:
+	///
+	/// ```text
+	/// worlds: 2
+	/// access: 1 -> 2, 2 -> 2
+	/// access a: 1 -> 1, 2 -> 2
+	/// value p: 2
+	/// ```
+	///
+	/// Blank lines and lines starting with `#` are ignored. The valuations can also be given
+	/// as `name: worlds` lines within a `value fn: {` ... `}` block, as in the model's `Debug` output,
+	/// whose braces, parentheses and headers are accepted too, so counter-models can be read back.
+	pub fn from_text(text: &str) -> Result<Model, String> {
+		#[derive(PartialEq)]
+		enum Block {Top, Valuations, Summary}
+		let mut r = RawModel::default();
+		// the valuations and the per world summary, which only repeats them, are in braces
+		let mut block = Block::Top;
+		for (i, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line == "}" && block != Block::Top {
+				block = Block::Top;
+				continue;
+			}
+			if block == Block::Summary || line.is_empty() || line.starts_with('#') || line == "Model:" {
+				continue;
+			}
+			let err = |e: String| format!("line {}: {}", i + 1, e);
+			let (key, value) = match line.find(':') {
+				Some(at) => (line[..at].trim(), &line[at+1..]),
+				None => return Err(err(format!("expected 'name: value', found '{}'", line))),
+			};
+			let numbers = |text: &str| -> Result<Vec<u32>, String> {
+				text.split(|c: char| c == ',' || c.is_whitespace() || "{}()".contains(c))
+				.filter(|x| !x.is_empty() && *x != "...")
+				.map(|x| x.parse::<u32>().map_err(|_| format!("expected a world, found '{}'", x)))
+				.collect()
+			};
+			let pairs = || -> Result<Vec<(u32, u32)>, String> {
+				let n = numbers(&value.replace("->", ","))?;
+				if n.len() % 2 != 0 {
+					return Err("expected pairs of worlds".to_owned());
+				}
+				Ok(n.chunks(2).map(|x| (x[0], x[1])).collect())
+			};
+			if block == Block::Valuations {
+				r.valuation(key, numbers(value).map_err(err)?).map_err(err)?;
+				continue;
+			}
+			let agent = key.strip_prefix("access fn ").or_else(|| key.strip_prefix("access "));
+			let variable = key.strip_prefix("value ");
+			match key {
+				"worlds" => r.num_worlds = numbers(value).map_err(err)?.into_iter().max(),
+				"access" | "access fn" => r.access(None, pairs().map_err(err)?),
+				_ if agent.is_some() => {
+					let agent = r.agent(agent.unwrap().trim()).map_err(err)?;
+					r.access(Some(agent), pairs().map_err(err)?);
+				},
+				"valuations" | "value fn" if value.trim() == "{" => block = Block::Valuations,
+				"per world" if value.trim() == "{" => block = Block::Summary,
+				_ if variable.is_some() => r.valuation(variable.unwrap().trim(), numbers(value).map_err(err)?).map_err(err)?,
+				_ => return Err(err(format!(
+					"unknown key '{}', expected one of worlds, access, access <agent>, value <variable>", key,
+				))),
+			}
+		}
+		r.finalize()
+	}
+
+	/// The variables true in the given world, in order.
+	pub fn true_in(&self, world: u32) -> Vec<Symbol> {
+		self.valuations.iter().filter(|x| x.1.contains(&world)).map(|x| *x.0).collect()
+	}
+
+	/// The variables of the model false in the given world, in order.
+	pub fn false_in(&self, world: u32) -> Vec<Symbol> {
+		self.valuations.iter().filter(|x| !x.1.contains(&world)).map(|x| *x.0).collect()
+	}
+
+	/// The agents with an accessibility relation of their own, in order.
+	pub fn agents(&self) -> impl Iterator<Item = Symbol> + '_ {
+		self.accessibility_functions.keys().filter_map(|&x| x)
+	}
+
+	/// The accessibility relation of the given agent, empty if it has none.
+	pub fn relation(&self, agent: Agent) -> &BTreeSet<(u32, u32)> {
+		static NONE: BTreeSet<(u32, u32)> = BTreeSet::new();
+		self.accessibility_functions.get(&agent).unwrap_or(&NONE)
+	}
+
+	/// The worlds the given world accesses by the given agent.
+	pub fn accessible_from(&self, agent: Agent, world: u32) -> impl Iterator<Item = u32> + '_ {
+		self.relation(agent).iter().filter(move |x| x.0 == world).map(|x| x.1)
+	}
+
+	/// Whether the model's frame satisfies all the given conditions, for the relation of every agent.
+	pub fn satisfies_frame_conditions(&self, conditions: FrameConditions) -> bool {
+		self.accessibility_functions.iter().all(|(&agent, r)| {
+			let worlds = 1..=self.num_worlds;
+			let from = |w| self.accessible_from(agent, w);
+			(!conditions.serial || worlds.clone().all(|w| from(w).next().is_some()))
+			&& (!conditions.reflexive || worlds.clone().all(|w| r.contains(&(w, w))))
+			&& (!conditions.symmetric || r.iter().all(|&(a, b)| r.contains(&(b, a))))
+			&& (!conditions.transitive || r.iter().all(|&(a, b)| from(b).all(|c| r.contains(&(a, c)))))
+			&& (!conditions.euclidean || r.iter().all(|&(a, b)| from(a).all(|c| r.contains(&(b, c)))))
+		})
+	}
+
+	/// A model as small as this one can be made in which `f` still fails in world 1, on a frame
+	/// still satisfying the given conditions, with the `global` formulae still holding everywhere.
+	/// Only the worlds reachable from world 1 are kept, the worlds and the truths the refutation
+	/// can do without are dropped, and bisimilar worlds, which no formula can tell apart, are merged.
+	/// If `f` doesn't fail in world 1 to begin with, the model is returned as it is.
+	pub fn minimized(&self, f: &Formula, global: &[Formula], conditions: FrameConditions) -> Model {
+		let refutes = |m: &Model| !m.satisfies(1, f)
+			&& global.iter().all(|g| m.satisfies_everywhere(g))
+			&& m.satisfies_frame_conditions(conditions);
+		if !refutes(self) {
+			return self.clone();
+		}
+		let mut m = self.restricted(&self.reachable_from(1));
+		// dropping a world only renumbers the ones after it, which have already been tried
+		for w in (2..=m.num_worlds).rev() {
+			let smaller = m.restricted(&(1..=m.num_worlds).filter(|&x| x != w).collect());
+			if refutes(&smaller) {
+				m = smaller;
+			}
+		}
+		let truths = m.valuations.iter()
+		.flat_map(|(&x, v)| v.iter().map(move |&w| (x, w)))
+		.collect::<Vec<_>>();
+		for (x, w) in truths {
+			m.valuations.get_mut(&x).unwrap().remove(&w);
+			if !refutes(&m) {
+				m.valuations.get_mut(&x).unwrap().insert(w);
+			}
+		}
+		let m = m.restricted(&m.reachable_from(1));
+		let classes = m.bisimulation_classes();
+		let merged = m.mapped(&classes.into_iter().map(Some).collect::<Vec<_>>());
+		if refutes(&merged) {
+			merged
+		} else {
+			m
+		}
+	}
+
+	// The worlds reachable from the given world by any agents, itself included.
+	fn reachable_from(&self, world: u32) -> BTreeSet<u32> {
+		let mut seen = BTreeSet::new();
+		let mut todo = vec![world];
+		while let Some(w) = todo.pop() {
+			if seen.insert(w) {
+				for &agent in self.accessibility_functions.keys() {
+					todo.extend(self.accessible_from(agent, w));
+				}
+			}
+		}
+		seen
+	}
+
+	// The part of the model made of the given worlds, numbered from 1 in the same order.
+	fn restricted(&self, worlds: &BTreeSet<u32>) -> Model {
+		let to = (1..=self.num_worlds)
+		.map(|w| worlds.iter().position(|&x| x == w).map(|i| i as u32 + 1))
+		.collect::<Vec<_>>();
+		self.mapped(&to)
+	}
+
+	// The model with each world w renamed to `to[w - 1]`, or dropped if that is None.
+	// Worlds renamed alike are merged, so they should agree on the valuations.
+	fn mapped(&self, to: &[Option<u32>]) -> Model {
+		let at = |w: u32| to[w as usize - 1];
+		Model {
+			num_worlds: to.iter().filter_map(|&x| x).max().unwrap_or(0),
+			accessibility_functions: self.accessibility_functions.iter()
+			.map(|(&agent, r)| (agent, r.iter().filter_map(|&(a, b)| Some((at(a)?, at(b)?))).collect()))
+			.collect(),
+			valuations: self.valuations.iter()
+			.map(|(&x, v)| (x, v.iter().filter_map(|&w| at(w)).collect()))
+			.collect(),
+		}
+	}
+
+	// The class of each world under the largest bisimulation of the model, with the classes
+	// numbered from 1 in the order of their first world. Starting from the worlds that agree
+	// on the valuations, classes are split until all the worlds in a class access the same classes
+	// by each agent.
+	fn bisimulation_classes(&self) -> Vec<u32> {
+		fn numbered<K: Ord>(keys: Vec<K>) -> Vec<u32> {
+			let mut seen = BTreeMap::new();
+			keys.into_iter().map(|k| {
+				let next = seen.len() as u32 + 1;
+				*seen.entry(k).or_insert(next)
+			}).collect()
+		}
+		let worlds = 1..=self.num_worlds;
+		let mut classes = numbered(worlds.clone().map(|w| self.true_in(w)).collect());
+		loop {
+			let refined = numbered(worlds.clone().map(|w| {
+				let accessed = self.accessibility_functions.keys()
+				.map(|&a| self.accessible_from(a, w).map(|v| classes[v as usize - 1]).collect::<BTreeSet<_>>())
+				.collect::<Vec<_>>();
+				(classes[w as usize - 1], accessed)
+			}).collect());
+			if refined == classes {
+				return classes;
+			}
+			classes = refined;
+		}
+	}
+}
+
+/// Reads a model in either its JSON or its textual form, see `Model::from_json` and `Model::from_text`.
+impl FromStr for Model {
+	type Err = String;
+	fn from_str(s: &str) -> Result<Model, String> {
+		if s.trim_start().starts_with('{') {
+			Model::from_json(&Json::parse(s)?)
+		} else {
+			Model::from_text(s)
+		}
+	}
+}
+
+// A model as read, before checking that its worlds are in range.
+#[derive(Default)]
+struct RawModel {
+	num_worlds: Option<u32>,
+	access: Vec<(Agent, u32, u32)>,
+	valuations: Vec<(Symbol, Vec<u32>)>,
+}
+
+impl RawModel {
+	fn access(&mut self, agent: Agent, pairs: Vec<(u32, u32)>) {
+		self.access.extend(pairs.into_iter().map(|(a, b)| (agent, a, b)));
+	}
+
+	fn agent(&self, name: &str) -> Result<Symbol, String> {
+		if !Symbol::is_valid_name(name) {
+			return Err(format!("'{}' is not an agent", name));
+		}
+		Ok(Symbol::intern(name))
+	}
+
+	fn valuation(&mut self, name: &str, worlds: Vec<u32>) -> Result<(), String> {
+		if !Symbol::is_valid_name(name) {
+			return Err(format!("'{}' is not a variable", name));
+		}
+		let x = Symbol::intern(name);
+		if self.valuations.iter().any(|v| v.0 == x) {
+			return Err(format!("{} is given a valuation twice", name));
 		}
+		self.valuations.push((x, worlds));
 		Ok(())
-    }
+	}
 
+	fn finalize(self) -> Result<Model, String> {
+		let used = self.access.iter().flat_map(|&(_, a, b)| vec![a, b])
+		.chain(self.valuations.iter().flat_map(|v| v.1.iter().cloned()));
+		let num_worlds = match self.num_worlds {
+			Some(n) => {
+				if let Some(w) = used.clone().find(|&w| w > n) {
+					return Err(format!("world {} is out of range, as there are only {} worlds", w, n));
+				}
+				n
+			},
+			None => used.clone().max().unwrap_or(1),
+		};
+		if num_worlds == 0 || used.clone().any(|w| w == 0) {
+			return Err("worlds are numbered from 1".to_owned());
+		}
+		let mut accessibility_functions = BTreeMap::new();
+		accessibility_functions.insert(None, BTreeSet::new());
+		for (agent, a, b) in self.access {
+			accessibility_functions.entry(agent).or_insert_with(BTreeSet::new).insert((a, b));
+		}
+		Ok(Model {
+			num_worlds,
+			accessibility_functions,
+			valuations: self.valuations.into_iter().map(|(k, v)| (k, v.into_iter().collect())).collect(),
+		})
+	}
+}
+
+// Writes `{a, b, c}`.
+fn write_set<T: fmt::Display, I: Iterator<Item = T>>(f: &mut fmt::Formatter, items: I) -> fmt::Result {
+	write!(f, "{{")?;
+	for (i, x) in items.enumerate() {
+		if i > 0 {write!(f, ", ")?;}
+		write!(f, "{}", x)?;
+	}
+	write!(f, "}}")
+}
+
+/// Writes out the model in full and in order, ending with the variables true and false in
+/// each world. `Model::from_text` reads the output back.
+impl fmt::Debug for Model {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Model:\n  worlds: ")?;
+		write_set(f, 1..=self.num_worlds)?;
+		for (agent, r) in self.accessibility_functions.iter() {
+			match *agent {
+				Some(x) => write!(f, "\n  access fn {}: ", x)?,
+				None => write!(f, "\n  access fn: ")?,
+			}
+			write_set(f, r.iter().map(|&(a, b)| format!("({}, {})", a, b)))?;
+		}
+		write!(f, "\n  value fn: {{")?;
+		for (k, v) in self.valuations.iter() {
+			write!(f, "\n    {}: ", k)?;
+			write_set(f, v.iter())?;
+		}
+		write!(f, "\n  }}\n  per world: {{")?;
+		for w in 1..=self.num_worlds {
+			write!(f, "\n    {}: true ", w)?;
+			write_set(f, self.true_in(w).into_iter())?;
+			write!(f, ", false ")?;
+			write_set(f, self.false_in(w).into_iter())?;
+		}
+		write!(f, "\n  }}")
+	}
 }
 
 #[derive(Debug)]
@@ -39,30 +481,264 @@ pub struct ModelBuilder {
 
 impl ModelBuilder {
 	pub fn new() -> ModelBuilder {
+		let mut accessibility_functions = BTreeMap::new();
+		accessibility_functions.insert(None, BTreeSet::new());
 		let m = Model {
 			num_worlds: 1,
-			accessibility_function: HashSet::new(),
-			valuations: HashMap::new(),
+			accessibility_functions,
+			valuations: BTreeMap::new(),
 		};
 		ModelBuilder {
-			m: m,
+			m,
 		}
 	}
 
-	pub fn add_access(&mut self, from: u32, to: u32) {
+	pub fn add_access(&mut self, agent: Agent, from: u32, to: u32) {
 		self.m.num_worlds = self.m.num_worlds.max(to.max(from));
-		self.m.accessibility_function.insert((from, to));
+		self.m.accessibility_functions.entry(agent).or_default().insert((from, to));
 	}
 
-	pub fn set_true_in(&mut self, world: u32, variable: char) {
+	/// Gives the agent an accessibility relation, even if it accesses nothing yet.
+	pub fn add_agent(&mut self, agent: Symbol) {
+		self.m.accessibility_functions.entry(Some(agent)).or_default();
+	}
+
+	pub fn set_true_in(&mut self, world: u32, variable: Symbol) {
 		self.m.num_worlds = self.m.num_worlds.max(world);
-		if !self.m.valuations.contains_key(&variable) {
-			self.m.valuations.insert(variable, HashSet::new());
+		self.m.valuations.entry(variable).or_default().insert(world);
+	}
+
+	/// Gives the variable an entry in the valuations, even if it is true nowhere.
+	pub fn add_variable(&mut self, variable: Symbol) {
+		self.m.valuations.entry(variable).or_default();
+	}
+
+	/// Makes every world access itself, by every agent.
+	pub fn make_reflexive(&mut self) {
+		for r in self.m.accessibility_functions.values_mut() {
+			for w in 1..=self.m.num_worlds {
+				r.insert((w, w));
+			}
+		}
+	}
+
+	/// Makes every world that accesses no world by an agent access itself by that agent.
+	pub fn make_serial(&mut self) {
+		for r in self.m.accessibility_functions.values_mut() {
+			for w in 1..=self.m.num_worlds {
+				if !r.iter().any(|x| x.0 == w) {
+					r.insert((w, w));
+				}
+			}
+		}
+	}
+
+	/// Makes every world access the worlds that access it, by the same agent.
+	pub fn make_symmetric(&mut self) {
+		for r in self.m.accessibility_functions.values_mut() {
+			let reversed = r.iter().map(|&(a, b)| (b, a)).collect::<Vec<_>>();
+			r.extend(reversed);
+		}
+	}
+
+	/// Makes every world access all the worlds its accessible worlds access, by the same agent.
+	pub fn make_transitive(&mut self) {
+		for r in self.m.accessibility_functions.values_mut() {
+			loop {
+				let mut new = vec![];
+				for &(a, b) in r.iter() {
+					for &(c, d) in r.iter() {
+						if b == c && !r.contains(&(a, d)) {
+							new.push((a, d));
+						}
+					}
+				}
+				if new.is_empty() {
+					break;
+				}
+				r.extend(new);
+			}
+		}
+	}
+
+	/// Makes any two worlds accessible from the same world by an agent access each other by that agent.
+	pub fn make_euclidean(&mut self) {
+		for r in self.m.accessibility_functions.values_mut() {
+			loop {
+				let mut new = vec![];
+				for &(a, b) in r.iter() {
+					for &(c, d) in r.iter() {
+						if a == c && !r.contains(&(b, d)) {
+							new.push((b, d));
+						}
+					}
+				}
+				if new.is_empty() {
+					break;
+				}
+				r.extend(new);
+			}
+		}
+	}
+
+	/// Adds as little access as needed for the model's frame to satisfy all the given conditions.
+	pub fn close_under(&mut self, conditions: FrameConditions) {
+		let size = |m: &Model| m.accessibility_functions.values().map(BTreeSet::len).sum::<usize>();
+		if conditions.reflexive {
+			self.make_reflexive();
+		}
+		loop {
+			let before = size(&self.m);
+			if conditions.symmetric {
+				self.make_symmetric();
+			}
+			if conditions.transitive {
+				self.make_transitive();
+			}
+			if conditions.euclidean {
+				self.make_euclidean();
+			}
+			if size(&self.m) == before {
+				break;
+			}
+		}
+		// worlds are left without access only where no diamonds are at stake, so they may as well access themselves
+		if conditions.serial {
+			self.make_serial();
 		}
-		self.m.valuations.get_mut(&variable).unwrap().insert(world);
 	}
 
 	pub fn finalize(self) -> Model {
 		self.m
 	}
 }
+
+impl Default for ModelBuilder {
+	fn default() -> ModelBuilder {
+		ModelBuilder::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::logics::Logic;
+	use ::parsing::parse;
+	use ::prove_in;
+
+	// Reads back the model's `Debug` output, and checks it reads as the same model.
+	fn round_trip(m: &Model) -> Model {
+		let text = format!("{:?}", m);
+		let back = Model::from_text(&text).unwrap_or_else(|e| panic!("{}\nin\n{}", e, text));
+		assert_eq!(format!("{:?}", back), text);
+		back
+	}
+
+	#[test]
+	fn dot_form() {
+		let m = Model::from_text("worlds: 3\naccess: 1 -> 2\naccess a: 2 -> 2\nvalue p: 1, 2\nvalue q: 2").unwrap();
+		assert_eq!(m.to_dot(), "\
+			digraph model {\n\
+			\tnode [shape=circle];\n\
+			\t1 [label=\"1\\np\"];\n\
+			\t2 [label=\"2\\np\\nq\"];\n\
+			\t3 [label=\"3\"];\n\
+			\t1 -> 2;\n\
+			\t2 -> 2 [label=\"a\"];\n\
+			}\n\
+		");
+	}
+
+	#[test]
+	fn text_form() {
+		let m = Model::from_text("\
+			# a comment\n\
+			worlds: 3\n\
+			access: 1 -> 2, 2 -> 2\n\
+			access a: 1 -> 1\n\
+			\n\
+			value p: 2, 3\n\
+			value q:\n\
+		").unwrap();
+		assert_eq!(m.num_worlds, 3);
+		assert_eq!(m.relation(None).iter().cloned().collect::<Vec<_>>(), vec![(1, 2), (2, 2)]);
+		assert_eq!(m.agents().map(|a| a.to_string()).collect::<Vec<_>>(), vec!["a"]);
+		assert_eq!(m.valuations[&Symbol::intern("p")], [2, 3].iter().cloned().collect());
+		assert!(m.valuations[&Symbol::intern("q")].is_empty());
+		round_trip(&m);
+	}
+
+	#[test]
+	fn variables_may_share_the_names_of_keys() {
+		let m = Model::from_text("worlds: 2\nvalue worlds: 1\nvalue access: 2\nvalue value: 1, 2").unwrap();
+		assert_eq!(m.num_worlds, 2);
+		assert!(m.relation(None).is_empty());
+		assert_eq!(m.valuations.len(), 3);
+		round_trip(&m);
+	}
+
+	#[test]
+	fn counter_models_read_back() {
+		for (f, logic) in &[
+			("<>worlds & <>-worlds -> q", "K"),
+			("<>p -> []p", "S5"),
+			("[]<>p -> <>[]p", "T"),
+			("[](p V q) -> []p V []q", "S4"),
+			("[a]p -> [b]p", "S4"),
+			("<a><b>p -> <b><a>p", "KD45"),
+		] {
+			let f = parse(f).unwrap();
+			let logic = logic.parse::<Logic>().unwrap();
+			let outcome = prove_in(&f, logic);
+			let m = round_trip(outcome.counter_model().unwrap());
+			assert!(!m.satisfies(1, &f));
+			assert!(m.satisfies_frame_conditions(logic.frame_conditions()));
+		}
+	}
+
+	#[test]
+	fn minimized_models_still_refute() {
+		let f = parse("<>p -> []p").unwrap();
+		let m = Model::from_text("worlds: 5\naccess: 1 -> 2, 1 -> 3, 1 -> 4, 4 -> 5\nvalue p: 2, 3, 5\nvalue q: 1, 2").unwrap();
+		let min = m.minimized(&f, &[], FrameConditions::default());
+		assert_eq!(format!("{:?}", min), format!("{:?}", Model::from_text("worlds: 3\naccess: 1 -> 2, 1 -> 3\nvalue p: 2\nvalue q:").unwrap()));
+		// in S5, every world must go on accessing every other
+		let s5 = Logic::S5.frame_conditions();
+		let m = prove_in(&f, Logic::S5).counter_model().unwrap().minimized(&f, &[], s5);
+		assert_eq!(m.num_worlds, 2);
+		assert!(m.satisfies_frame_conditions(s5) && !m.satisfies(1, &f));
+		// a model that doesn't refute the formula is left as it is
+		let g = parse("p -> p").unwrap();
+		assert_eq!(format!("{:?}", m.minimized(&g, &[], s5)), format!("{:?}", m));
+	}
+
+	#[test]
+	fn minimizing_merges_bisimilar_worlds() {
+		// in serial frames no world of the cycle can be dropped, but all of them can be merged into one
+		let m = Model::from_text("worlds: 3\naccess: 1 -> 2, 2 -> 3, 3 -> 2\nvalue p: 3").unwrap();
+		let min = m.minimized(&parse("p").unwrap(), &[], Logic::KD.frame_conditions());
+		assert_eq!(format!("{:?}", min), format!("{:?}", Model::from_text("worlds: 1\naccess: 1 -> 1\nvalue p:").unwrap()));
+	}
+
+	#[test]
+	fn json_form_reads_back() {
+		let m = Model::from_text("worlds: 3\naccess: 1 -> 2\naccess b: 2 -> 3, 3 -> 3\nvalue p: 3\nvalue q:").unwrap();
+		let back = Model::from_json(&Json::parse(&m.to_json().to_string()).unwrap()).unwrap();
+		assert_eq!(format!("{:?}", back), format!("{:?}", m));
+	}
+
+	#[test]
+	fn text_errors() {
+		let err = |text: &str| Model::from_text(text).unwrap_err();
+		assert_eq!(err("acess: 1 -> 2"), "line 1: unknown key 'acess', expected one of worlds, access, access <agent>, value <variable>");
+		assert_eq!(err("p: 1"), "line 1: unknown key 'p', expected one of worlds, access, access <agent>, value <variable>");
+		assert_eq!(err("value p: 1 -> 2"), "line 1: expected a world, found '->'");
+		assert_eq!(err("value fn: {\n  p: 1 -> 2\n}"), "line 2: expected a world, found '->'");
+		assert_eq!(err("access: 1 -> 2, 3"), "line 1: expected pairs of worlds");
+		assert_eq!(err("worlds: 2\naccess: 1 -> 3"), "world 3 is out of range, as there are only 2 worlds");
+		assert_eq!(err("value p: 0"), "worlds are numbered from 1");
+		assert_eq!(err("value p: 1\nvalue p: 2"), "line 2: p is given a valuation twice");
+		assert_eq!(err("value P: 1"), "line 1: 'P' is not a variable");
+		assert_eq!(err("worlds 2"), "line 1: expected 'name: value', found 'worlds 2'");
+	}
+}
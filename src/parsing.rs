@@ -1,146 +1,331 @@
 use ::formulae::*;
+use ::lexing::{tokenize, Token, TokenKind};
+use ::sequents::Sequent;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
 
-fn has_redundant_brackets(s: &str) -> bool {
-	if s.len() < 2
-	|| !s.starts_with('(')
-	|| !s.ends_with(')') {
-		return false;
-	}
-	let mut depth = 1;
-	for c in s[1..s.len()-1].chars() {
-		match c {
-			'(' => depth += 1,
-			')' => {
-				depth -= 1;
-				if depth == 0 {
-					return false;
-				}
+/// Why and where an input failed to parse.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+	/// Byte range of the problem within the original input.
+	pub span: Range<usize>,
+	/// The text at `span`, if the problem is a token rather than a missing one.
+	pub token: Option<String>,
+	/// What the parser was expecting, e.g. "expected operand after ∧".
+	pub expectation: String,
+}
+
+impl ParseError {
+	/// Writes out the message, followed by the input with a caret under the problem.
+	pub fn render(&self, input: &str) -> String {
+		let column = input[..self.span.start].chars().count();
+		let width = input[self.span.clone()].chars().count().max(1);
+		format!("{}\n  {}\n  {}{}", self, input, " ".repeat(column), "^".repeat(width))
+	}
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", &self.expectation)?;
+		match self.token {
+			Some(ref t) if self.expectation.starts_with("expected") && !self.expectation.ends_with(&format!(" {}", t)) => {
+				write!(f, ", found '{}'", t)
 			},
-			_ => (),
+			_ => Ok(()),
 		}
 	}
-	true
 }
 
-pub fn parse(mut s: &str) -> Option<Formula> {
-	while has_redundant_brackets(s) {
-		s = &s[1..s.len()-1];
+impl Error for ParseError {}
+
+/// Parses a formula given in any mix of ascii and unicode notation.
+///
+/// Prefix operators (¬, ◇, □, and the `<a>` and `[a]` of an agent a) bind tightest, followed by ∧, then ↑ (nand),
+/// then ∨, then ↓ (nor), then ⊕, then →, then ↔.
+/// ∧, ↑, ∨, ↓, ⊕ and ↔ associate to the left, while → associates to the right,
+/// so `p→q→r` reads as `p→(q→r)`.
+pub fn parse(input: &str) -> Result<Formula, ParseError> {
+	let mut parser = Parser {
+		tokens: tokenize(input)?,
+		next: 0,
+		input_len: input.len(),
+	};
+	let f = parser.formula(0, "expected formula")?;
+	match parser.peek() {
+		None => Ok(f),
+		Some(t) => Err(match t.kind {
+			TokenKind::RightParen => parser.error_at(t, "unbalanced ')'"),
+			_ => parser.error_at(t, "expected operator"),
+		}),
+	}
+}
+
+/// Parses a sequent `Γ ⇒ Δ`, in which Γ and Δ are lists of formulae separated by commas,
+/// either of which may be empty, such as `□p, ◇q ⇒ ◇(p∧q), r`. Input without ⇒ is read
+/// as a single formula φ, giving the sequent `⇒ φ`.
+///
+/// The formulae are kept as they are written, to be preprocessed by the prover.
+pub fn parse_sequent(input: &str) -> Result<Sequent, ParseError> {
+	let mut parser = Parser {
+		tokens: tokenize(input)?,
+		next: 0,
+		input_len: input.len(),
+	};
+	let left = parser.list()?;
+	let arrow = parser.peek().map(|t| t.kind == TokenKind::SequentArrow).unwrap_or(false);
+	if arrow {
+		parser.next += 1;
+	}
+	let right = if arrow {parser.list()?} else {vec![]};
+	match parser.peek() {
+		None if arrow => Ok(Sequent::new(left, right)),
+		None if left.len() == 1 => Ok(Sequent::new(vec![], left)),
+		None if left.is_empty() => Err(parser.error_at_end("expected formula")),
+		None => Err(parser.error_at_end(&format!("expected {} after a list of formulae", TokenKind::SequentArrow.symbol()))),
+		Some(t) => Err(match t.kind {
+			TokenKind::RightParen => parser.error_at(t, "unbalanced ')'"),
+			TokenKind::SequentArrow => parser.error_at(t, "expected a single ⇒"),
+			_ => parser.error_at(t, "expected operator"),
+		}),
 	}
-	use Formula::*;
-	if s.chars().count() == 1 {
-		return match s.chars().next() {
-			Some('T') => Some(Top),
-			Some('F') => Some(Bottom),
-			Some(x) if x.is_lowercase() => Some(Letter(x)),
+}
+
+struct Parser {
+	tokens: Vec<Token>,
+	next: usize,
+	input_len: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.next)
+	}
+
+	fn error_at(&self, t: &Token, expectation: &str) -> ParseError {
+		ParseError {
+			span: t.span.clone(),
+			token: Some(t.kind.symbol()),
+			expectation: expectation.to_owned(),
+		}
+	}
+
+	fn error_at_end(&self, expectation: &str) -> ParseError {
+		ParseError {
+			span: self.input_len..self.input_len,
+			token: None,
+			expectation: expectation.to_owned(),
+		}
+	}
+
+	// binding power and right-associativity of the binary operators
+	fn infix(kind: TokenKind) -> Option<(u8, bool)> {
+		match kind {
+			TokenKind::Conjunction => Some((FormulaType::Conjunction.bind_strength(), false)),
+			TokenKind::Disjunction => Some((FormulaType::Disjunction.bind_strength(), false)),
+			TokenKind::Implication => Some((FormulaType::Implication.bind_strength(), true)),
+			TokenKind::Biconditional => Some((FormulaType::Biconditional.bind_strength(), false)),
+			TokenKind::Xor => Some((FormulaType::Xor.bind_strength(), false)),
+			TokenKind::Nand => Some((FormulaType::Nand.bind_strength(), false)),
+			TokenKind::Nor => Some((FormulaType::Nor.bind_strength(), false)),
 			_ => None,
 		}
 	}
-	let mut depth = 0;
-	let mut best = FormulaType::None;
-	let mut best_index = 1337;
-	for (i, c) in s.char_indices() {
-		if depth > 0 {
-			match c {
-				'(' => depth += 1,
-				')' => depth -= 1,
-				_ => (),
-			}
-		} else {
-			match c {
-				'(' => depth += 1,
-				')' => return None,
-				'¬' if i == 0 => {
-					if best.bind_strength() > FormulaType::Negation.bind_strength() {
-						best = FormulaType::Negation;
-						best_index = i;
-					}
-				}
-				'◇' if i == 0 => {
-					if best.bind_strength() > FormulaType::MDiamond.bind_strength() {
-						best = FormulaType::MDiamond;
-						best_index = i;
-					}
-				}
-				'□' if i == 0 => {
-					if best.bind_strength() > FormulaType::MBox.bind_strength() {
-						best = FormulaType::MBox;
-						best_index = i;
-					}
-				}
-				'→' => {
-					if best.bind_strength() > FormulaType::Implication.bind_strength() {
-						best = FormulaType::Implication;
-						best_index = i;
-					}
-				}
-				'∧' => {
-					if best.bind_strength() > FormulaType::Conjunction.bind_strength() {
-						best = FormulaType::Conjunction;
-						best_index = i;
-					}
-				}
-				'∨' => {
-					if best.bind_strength() > FormulaType::Disjunction.bind_strength() {
-						best = FormulaType::Disjunction;
-						best_index = i;
-					}
-				}
-				_ => (),
-			}
+
+	// Parses the longest formula whose binary operators all bind at least as strongly as `min_strength`.
+	// `missing` describes what was expected should there be no formula at all.
+	fn formula(&mut self, min_strength: u8, missing: &str) -> Result<Formula, ParseError> {
+		let mut lhs = self.operand(missing)?;
+		while let Some(t) = self.peek().cloned() {
+			let (strength, right_assoc) = match Parser::infix(t.kind) {
+				Some(x) if x.0 >= min_strength => x,
+				_ => break,
+			};
+			self.next += 1;
+			let next_min = if right_assoc {strength} else {strength + 1};
+			let rhs = self.formula(next_min, &format!("expected operand after {}", t.kind.symbol()))?;
+			lhs = match t.kind {
+				TokenKind::Conjunction => Formula::Conjunction(Box::new(lhs), Box::new(rhs)),
+				TokenKind::Disjunction => Formula::Disjunction(Box::new(lhs), Box::new(rhs)),
+				TokenKind::Biconditional => Formula::Biconditional(Box::new(lhs), Box::new(rhs)),
+				TokenKind::Xor => Formula::Xor(Box::new(lhs), Box::new(rhs)),
+				TokenKind::Nand => Formula::Nand(Box::new(lhs), Box::new(rhs)),
+				TokenKind::Nor => Formula::Nor(Box::new(lhs), Box::new(rhs)),
+				_ => Formula::Implication(Box::new(lhs), Box::new(rhs)),
+			};
+		}
+		Ok(lhs)
+	}
+
+	// Parses formulae separated by commas, up to the end of the input or a ⇒. There may be none.
+	fn list(&mut self) -> Result<Vec<Formula>, ParseError> {
+		let mut v = vec![];
+		match self.peek() {
+			None | Some(&Token {kind: TokenKind::SequentArrow, ..}) => return Ok(v),
+			_ => (),
 		}
+		v.push(self.formula(0, "expected formula")?);
+		while let Some(&Token {kind: TokenKind::Comma, ..}) = self.peek() {
+			self.next += 1;
+			v.push(self.formula(0, "expected formula after ,")?);
+		}
+		Ok(v)
 	}
-	match best {
-		FormulaType::None => None,
-		FormulaType::Negation => {
-			parse(&s[best_index + '¬'.len_utf8()..])
-			.map(|x| Negation(Box::new(x)))
-		},
-		FormulaType::MDiamond => {
-			parse(&s[best_index + '◇'.len_utf8()..])
-			.map(|x| MDiamond(Box::new(x)))
-		},
-		FormulaType::MBox => {
-			parse(&s[best_index + '□'.len_utf8()..])
-			.map(|x| MBox(Box::new(x)))
-		},
-		FormulaType::Implication => {
-			let a = parse(&s[..best_index]);
-			let b = parse(&s[best_index + '→'.len_utf8()..]);
-			if let (Some(x), Some(y)) = (a,b) {
-				Some(Implication(Box::new(x), Box::new(y)))
-			} else {None}
-		},
-		FormulaType::Conjunction => {
-			let a = parse(&s[..best_index]);
-			let b = parse(&s[best_index + '∧'.len_utf8()..]);
-			if let (Some(x), Some(y)) = (a,b) {
-				Some(Conjunction(Box::new(x), Box::new(y)))
-			} else {None}
-		},
-		FormulaType::Disjunction => {
-			let a = parse(&s[..best_index]);
-			let b = parse(&s[best_index + '∨'.len_utf8()..]);
-			if let (Some(x), Some(y)) = (a,b) {
-				Some(Disjunction(Box::new(x), Box::new(y)))
-			} else {None}
-		},
-		_ => None,
+
+	// Parses a formula without any unparenthesized binary operators.
+	fn operand(&mut self, missing: &str) -> Result<Formula, ParseError> {
+		let t = match self.peek().cloned() {
+			Some(t) => t,
+			None => return Err(self.error_at_end(missing)),
+		};
+		self.next += 1;
+		use self::TokenKind::*;
+		match t.kind {
+			Letter(x) => Ok(Formula::Letter(x)),
+			Top => Ok(Formula::Top),
+			Bottom => Ok(Formula::Bottom),
+			Negation | MDiamond(_) | MBox(_) => {
+				let x = Box::new(self.operand(&format!("expected operand after {}", t.kind.symbol()))?);
+				Ok(match t.kind {
+					MDiamond(a) => Formula::MDiamond(a, x),
+					MBox(a) => Formula::MBox(a, x),
+					_ => Formula::Negation(x),
+				})
+			},
+			LeftParen => {
+				let x = self.formula(0, "expected formula after (")?;
+				match self.peek() {
+					Some(&Token {kind: RightParen, ..}) => {
+						self.next += 1;
+						Ok(x)
+					},
+					Some(u) if Parser::infix(u.kind).is_none() && u.kind != SequentArrow && u.kind != Comma => Err(self.error_at(u, "expected operator")),
+					_ => Err(self.error_at(&t, "unbalanced '('")),
+				}
+			},
+			Conjunction | Disjunction | Implication | Biconditional | Xor | Nand | Nor if self.next == 1 => {
+				Err(self.error_at(&t, &format!("expected operand before {}", t.kind.symbol())))
+			},
+			Conjunction | Disjunction | Implication | Biconditional | Xor | Nand | Nor | RightParen | SequentArrow | Comma => {
+				Err(self.error_at(&t, missing))
+			},
+		}
 	}
 }
 
-pub fn to_unicode(s: String) -> String {
-	s
-	.replace("->", "→")
-	.replace("=>", "⇒")
-	.replace("-", "¬")
-	.replace("~", "¬")
-	.replace("/\\", "∧")
-	.replace("&", "∧")
-	.replace("V", "∨")
-	.replace("\\/", "∨")
-	.replace("<>", "◇")
-	.replace("[]", "□")
-	.replace(" ", "")
-	.replace("⊤", "T")
-	.replace("⊥", "F")
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::rendering::{Notation, RenderOptions};
+
+	fn p(input: &str) -> Formula {
+		parse(input).unwrap()
+	}
+
+	fn err(input: &str) -> (Range<usize>, String) {
+		let e = parse(input).unwrap_err();
+		(e.span.clone(), e.to_string())
+	}
+
+	#[test]
+	fn binary_operators_bind_in_order() {
+		// ∧ binds tighter than ∨, so this isn't p∧(q∨r)
+		assert_eq!(p("p&q V r"), p("(p&q)Vr"));
+		assert_eq!(p("p V q&r"), p("pV(q&r)"));
+		assert_eq!(p("p V q->r"), p("(pVq)->r"));
+		assert_eq!(p("p&q -> r V s"), p("(p&q)->(rVs)"));
+		assert_eq!(p("p&q!&r"), p("(p&q)!&r"));
+		assert_eq!(p("p!&q V r"), p("(p!&q)Vr"));
+		assert_eq!(p("p V q!V r"), p("(pVq)!Vr"));
+		assert_eq!(p("p!V q<+>r"), p("(p!Vq)<+>r"));
+		assert_eq!(p("p<+>q->r"), p("(p<+>q)->r"));
+		assert_eq!(p("p<->q->r"), p("p<->(q->r)"));
+		assert_eq!(p("p&q -> r V s <-> t"), p("((p&q)->(rVs))<->t"));
+	}
+
+	#[test]
+	fn prefix_operators_bind_tightest() {
+		assert_eq!(p("-p&q"), p("(-p)&q"));
+		assert_eq!(p("<>p->[]q"), p("(<>p)->([]q)"));
+		assert_eq!(p("[]-<>p"), p("[](-(<>p))"));
+		assert_eq!(p("[a]-<b>p"), p("[a](-(<b>p))"));
+		assert_eq!(p("-(p&q)"), Formula::Negation(Box::new(p("p&q"))));
+	}
+
+	#[test]
+	fn implication_associates_to_the_right() {
+		assert_eq!(p("p->q->r"), p("p->(q->r)"));
+		assert!(p("p->q->r") != p("(p->q)->r"));
+	}
+
+	#[test]
+	fn the_others_associate_to_the_left() {
+		assert_eq!(p("p&q&r"), p("(p&q)&r"));
+		assert_eq!(p("pVqVr"), p("(pVq)Vr"));
+		for op in &["<->", "<+>", "!&", "!V"] {
+			assert_eq!(p(&format!("p{0}q{0}r", op)), p(&format!("(p{0}q){0}r", op)), "{}", op);
+		}
+		assert!(p("p&q&r") != p("p&(q&r)"));
+	}
+
+	#[test]
+	fn formulae_read_back_as_they_are_written() {
+		for input in &["(p&q)Vr", "pV(q&r)", "p->(q->r)", "(p->q)->r", "[]<>-p->T", "[a]<>-p<->T", "-(p&F)", "door_open2V-x_", "p!&(q!&r)", "(p!Vq)!Vr", "p!&q!Vr", "(pVq)!&r", "p<->q<+>r", "(p<->q)<->r"] {
+			assert_eq!(p(input).to_string(), *input);
+		}
+	}
+
+	#[test]
+	fn connectives_print_in_every_notation() {
+		let f = p("(p<->q)<+>(p!&q)!Vr");
+		let unicode = RenderOptions::new(Notation::Unicode);
+		let latex = RenderOptions::new(Notation::Latex);
+		assert_eq!(f.display(&unicode).to_string(), "(p↔q)⊕p↑q↓r");
+		assert_eq!(p(&f.display(&unicode).to_string()), f);
+		assert_eq!(f.display(&latex).to_string(), "(p \\leftrightarrow q) \\oplus p \\uparrow q \\downarrow r");
+	}
+
+	#[test]
+	fn errors_point_at_the_problem() {
+		assert_eq!(err("p q"), (2..3, "expected operator, found 'q'".to_owned()));
+		// 'r' ends "expected operator" but is no word of it
+		assert_eq!(err("p r"), (2..3, "expected operator, found 'r'".to_owned()));
+		assert_eq!(err("p &"), (3..3, "expected operand after ∧".to_owned()));
+		assert_eq!(err("& p"), (0..1, "expected operand before ∧".to_owned()));
+		assert_eq!(err("(p & q"), (0..1, "unbalanced '('".to_owned()));
+		assert_eq!(err("p & q)"), (5..6, "unbalanced ')'".to_owned()));
+		assert_eq!(err(""), (0..0, "expected formula".to_owned()));
+		assert_eq!(err("p => q"), (2..4, "expected operator, found '⇒'".to_owned()));
+	}
+
+	#[test]
+	fn errors_render_with_a_caret() {
+		let input = "¬p ∧ ∧ q";
+		let e = parse(input).unwrap_err();
+		assert_eq!(e.render(input), "expected operand after ∧\n  ¬p ∧ ∧ q\n       ^");
+		let input = "¬p ∧ q s";
+		let e = parse(input).unwrap_err();
+		assert_eq!(e.render(input), "expected operator, found 's'\n  ¬p ∧ q s\n         ^");
+	}
+
+	#[test]
+	fn sequents_have_lists_on_either_side() {
+		let s = parse_sequent("[]p, <>q => <>(p&q), r").unwrap();
+		assert_eq!(s.left(), &[p("[]p"), p("<>q")][..]);
+		assert_eq!(s.right(), &[p("<>(p&q)"), p("r")][..]);
+		let s = parse_sequent("p->p").unwrap();
+		assert!(s.left().is_empty());
+		assert_eq!(s.right(), &[p("p->p")][..]);
+		assert!(parse_sequent("=>").unwrap().right().is_empty());
+	}
+
+	#[test]
+	fn sequent_errors() {
+		let e = parse_sequent("p, q").unwrap_err();
+		assert_eq!(e.to_string(), "expected ⇒ after a list of formulae");
+		let e = parse_sequent("p => q => r").unwrap_err();
+		assert_eq!((e.span.clone(), e.to_string()), (7..9, "expected a single ⇒".to_owned()));
+		let e = parse_sequent("p, => q").unwrap_err();
+		assert_eq!((e.span.clone(), e.to_string()), (3..5, "expected formula after ,, found '⇒'".to_owned()));
+	}
+}
@@ -1,19 +1,48 @@
-
 use ::sequents::{Sequent, StepResult};
+use ::json::Json;
+use ::logics::{FrameConditions, Logic};
+use ::rendering::{dot_quoted, Notation, Render, RenderOptions, Rendered};
+use ::symbols::Symbol;
 use std::collections::HashSet;
+use std::fmt;
+use std::time::Instant;
+
+/// A single rule application and the sequent it produced.
+pub struct Step {
+	pub rule: &'static str,
+	pub sequent: Sequent,
+}
 
 pub struct Proof {
-	steps: Vec<String>,
+	start: Sequent,
+	steps: Vec<Step>,
+	branch_rule: Option<&'static str>,
 	proof_result: ProofResult,
-	true_here: HashSet<char>,
+	true_here: HashSet<Symbol>,
 	valid: bool,
 }
 
+/// The error of a proof that was given up on, as its deadline passed before it was done.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct DeadlinePassed;
+
+impl fmt::Display for DeadlinePassed {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "the proof was not done by its deadline")
+	}
+}
+
 pub enum ProofResult {
 	Valid,
 	Invalid,
 	AnyValid(Vec<Proof>),
 	BothValid(Box<Proof>, Box<Proof>),
+	/// The sequent is the same as the one the world at the given depth started with,
+	/// so proving it would only repeat that world's proof. Counts as invalid.
+	Loop(usize),
+	/// The sequent was left unproved, as the verdict of the proof that needed it
+	/// had already been settled by another. Counts as invalid.
+	Skipped,
 }
 
 impl Proof {
@@ -21,7 +50,7 @@ impl Proof {
 		&self.proof_result
 	}
 
-	pub fn true_here(&self) -> &HashSet<char> {
+	pub fn true_here(&self) -> &HashSet<Symbol> {
 		&self.true_here
 	}
 
@@ -29,79 +58,315 @@ impl Proof {
 		self.valid
 	}
 
-	pub fn new(mut m: Sequent) -> Proof {
-		let mut steps = vec![format!("* Prove: {:?}", &m)];
+	/// The sequent this proof set out to prove.
+	pub fn start(&self) -> &Sequent {
+		&self.start
+	}
+
+	/// The non-branching rule applications, in order.
+	pub fn steps(&self) -> &[Step] {
+		&self.steps
+	}
+
+	/// The rule that split this proof into the inner proofs of its `ProofResult`, if any.
+	pub fn branch_rule(&self) -> Option<&'static str> {
+		self.branch_rule
+	}
+
+	fn skipped(start: Sequent) -> Proof {
+		Proof {
+			start,
+			steps: vec![],
+			branch_rule: None,
+			proof_result: ProofResult::Skipped,
+			true_here: HashSet::new(),
+			valid: false,
+		}
+	}
+
+	pub fn display<'a>(&'a self, opts: &'a RenderOptions) -> Rendered<'a, Proof> {
+		Rendered::new(self, opts)
+	}
+
+	/// Attempts to prove `m` valid in all frames of the given logic.
+	pub fn new(m: Sequent, logic: Logic) -> Proof {
+		Proof::prove_root(m, logic, None).unwrap()
+	}
+
+	/// Attempts to prove `m` valid in all frames of the given logic, giving up at the deadline.
+	pub fn new_before(m: Sequent, logic: Logic, deadline: Instant) -> Result<Proof, DeadlinePassed> {
+		Proof::prove_root(m, logic, Some(deadline))
+	}
+
+	fn prove_root(mut m: Sequent, logic: Logic, deadline: Option<Instant>) -> Result<Proof, DeadlinePassed> {
+		let conditions = logic.frame_conditions();
+		if conditions.euclidean {
+			// the worlds of a cluster agree on which diamonds hold, so decide them all up front
+			m.cut_modal(conditions);
+		} else if conditions.symmetric {
+			m.cut_bodies();
+		}
+		let mut history = vec![m.clone()];
+		Proof::prove(m, conditions, &mut history, deadline)
+	}
+
+	// `history` holds the sequents each world along the path from the root world started with.
+	// Rules like those of S4 carry formulae into accessible worlds, so without checking
+	// against this history the search could go on creating worlds forever.
+	fn prove(mut m: Sequent, conditions: FrameConditions, history: &mut Vec<Sequent>, deadline: Option<Instant>) -> Result<Proof, DeadlinePassed> {
+		let start = m.clone();
+		let mut steps = vec![];
 		loop {
 			use self::StepResult::*;
-			match m.step() {
-				Indeterminate(r, a) => {
-					steps.push(format!("  [{}] {:?}", r, &a));
+			if deadline.is_some_and(|x| Instant::now() >= x) {
+				return Err(DeadlinePassed);
+			}
+			match m.step(conditions) {
+				Indeterminate(rule, a) => {
+					steps.push(Step {
+						rule,
+						sequent: a.clone(),
+					});
 					m = a;
 				},
 				Valid(letters_on_left) => {
-					steps.push(format!("  valid!"));
-					return Proof {
-						steps: steps,
+					return Ok(Proof {
+						start,
+						steps,
+						branch_rule: None,
 						proof_result: ProofResult::Valid,
 						true_here: letters_on_left,
 						valid: true,
-					}
+					})
 				},
 				Invalid(letters_on_left) => {
-					steps.push(format!("  invalid!"));
-					return Proof {
-						steps: steps,
+					return Ok(Proof {
+						start,
+						steps,
+						branch_rule: None,
 						proof_result: ProofResult::Invalid,
 						true_here: letters_on_left,
 						valid: false,
-					}
+					})
 				},
 				ValidIfAny(r, v, letters_on_left) => {
-					let proofs = v.into_iter().map(|x| Proof::new(x)).collect::<Vec<_>>();
-					let valid = proofs.iter().fold(false, |a,b| a||b.valid);
-					steps.push(format!("  [{}] valid if any... ({})", r, if valid {"valid"} else {"invalid"}));
-					return Proof {
-						steps: steps,
+					let mut valid = false;
+					let proofs = v.into_iter().map(|x| {
+						// one valid inner proof is enough
+						if valid {
+							return Ok(Proof::skipped(x));
+						}
+						if let Some(depth) = history.iter().position(|h| h.same_formulae(&x)) {
+							return Ok(Proof {
+								start: x,
+								steps: vec![],
+								branch_rule: None,
+								proof_result: ProofResult::Loop(depth),
+								true_here: HashSet::new(),
+								valid: false,
+							})
+						}
+						history.push(x.clone());
+						let p = Proof::prove(x, conditions, history, deadline);
+						history.pop();
+						valid = p.as_ref().is_ok_and(|p| p.valid);
+						p
+					}).collect::<Result<Vec<_>, _>>()?;
+					return Ok(Proof {
+						start,
+						steps,
+						branch_rule: Some(r),
 						proof_result: ProofResult::AnyValid(proofs),
 						true_here: letters_on_left,
-						valid: valid,
-					}
+						valid,
+					})
 				},
 				ValidIfBoth(r, a, b, letters_on_left) => {
-					let a = Box::new(Proof::new(a));
-					let b = Box::new(Proof::new(b));
+					let a = Box::new(Proof::prove(*a, conditions, history, deadline)?);
+					// one invalid inner proof is enough
+					let b = Box::new(if a.valid {
+						Proof::prove(*b, conditions, history, deadline)?
+					} else {
+						Proof::skipped(*b)
+					});
 					let valid = a.valid && b.valid;
-					steps.push(format!("  [{}] valid if both... ({})", r, if valid {"valid"} else {"invalid"}));
-					return Proof {
-						steps: steps,
+					return Ok(Proof {
+						start,
+						steps,
+						branch_rule: Some(r),
 						proof_result: ProofResult::BothValid(a, b),
 						true_here: letters_on_left,
-						valid: valid,
-					}
+						valid,
+					})
 				},
 			}
 		}
 	}
 
-	pub fn print(&self, depth: u8) {
+	fn render_at(&self, f: &mut fmt::Formatter, opts: &RenderOptions, depth: u8) -> fmt::Result {
+		let indent = "    ".repeat(depth as usize);
+		writeln!(f, "{}* Prove: {}", indent, self.start.display(opts))?;
 		for s in self.steps.iter() {
-			for _ in 0..depth {print!("    ")}
-			println!("{}", &s)
+			writeln!(f, "{}  [{}] {}", indent, s.rule, s.sequent.display(opts))?;
 		}
+		let verdict = if self.valid {"valid"} else {"invalid"};
 		use self::ProofResult::*;
 		match self.proof_result {
-			Valid => (),
-			Invalid => (),
+			Valid => writeln!(f, "{}  valid!", indent),
+			Invalid => writeln!(f, "{}  invalid!", indent),
+			Loop(depth) => writeln!(f, "{}  loop! (same as the world at depth {})", indent, depth),
+			Skipped => writeln!(f, "{}  skipped!", indent),
 			AnyValid(ref v) => {
+				writeln!(f, "{}  [{}] valid if any... ({})", indent, self.branch_rule.unwrap_or("?"), verdict)?;
 				for q in v.iter() {
-					q.print(depth+1);
+					q.render_at(f, opts, depth+1)?;
 				}
+				Ok(())
+			},
+			BothValid(ref a, ref b) => {
+				writeln!(f, "{}  [{}] valid if both... ({})", indent, self.branch_rule.unwrap_or("?"), verdict)?;
+				a.render_at(f, opts, depth+1)?;
+				b.render_at(f, opts, depth+1)
+			},
+		}
+	}
+
+	/// The proof as a JSON object, with its inner proofs nested inside. See the readme for its schema.
+	pub fn to_json(&self, opts: &RenderOptions) -> Json {
+		use self::ProofResult::*;
+		let steps = self.steps.iter().map(|x| Json::Object(vec![
+			("rule".to_owned(), Json::String(x.rule.to_owned())),
+			("sequent".to_owned(), x.sequent.to_json(opts)),
+		])).collect();
+		let mut members = vec![
+			("sequent".to_owned(), self.start.to_json(opts)),
+			("steps".to_owned(), Json::Array(steps)),
+		];
+		let (kind, inner) = match self.proof_result {
+			Valid => ("valid", None),
+			Invalid => ("invalid", None),
+			Skipped => ("skipped", None),
+			Loop(depth) => {
+				members.push(("loop_depth".to_owned(), Json::Number(depth as f64)));
+				("loop", None)
+			},
+			AnyValid(ref v) => ("any_valid", Some(v.iter().collect::<Vec<_>>())),
+			BothValid(ref a, ref b) => ("both_valid", Some(vec![&**a, &**b])),
+		};
+		members.push(("result".to_owned(), Json::String(kind.to_owned())));
+		if let Some(inner) = inner {
+			members.push(("branch_rule".to_owned(), Json::String(self.branch_rule.unwrap_or("?").to_owned())));
+			members.push(("proofs".to_owned(), Json::Array(inner.into_iter().map(|x| x.to_json(opts)).collect())));
+		}
+		members.push(("valid".to_owned(), Json::Bool(self.valid)));
+		Json::Object(members)
+	}
+
+	/// The branching structure of the proof as a directed graph in Graphviz's DOT language.
+	/// Every proof is a node labelled with the sequent it starts with and how it ended,
+	/// with edges to its inner proofs labelled with the rule that needed them.
+	pub fn to_dot(&self, opts: &RenderOptions) -> String {
+		let mut out = "digraph proof {\n\tnode [shape=box];\n".to_owned();
+		self.dot_at(&mut out, &mut 0, opts);
+		out.push_str("}\n");
+		out
+	}
+
+	// Writes this proof as node `next`, and its inner proofs as the nodes after it.
+	fn dot_at(&self, out: &mut String, next: &mut u32, opts: &RenderOptions) {
+		use self::ProofResult::*;
+		let id = *next;
+		*next += 1;
+		let ending = match self.proof_result {
+			Valid => "valid!".to_owned(),
+			Invalid => "invalid!".to_owned(),
+			Skipped => "skipped!".to_owned(),
+			Loop(depth) => format!("loop! (same as the world at depth {})", depth),
+			AnyValid(_) => format!("valid if any... ({})", if self.valid {"valid"} else {"invalid"}),
+			BothValid(_, _) => format!("valid if both... ({})", if self.valid {"valid"} else {"invalid"}),
+		};
+		let label = format!("{}\n{}", self.start.display(opts), ending);
+		out.push_str(&format!("\t{} [label={}];\n", id, dot_quoted(&label)));
+		let inner: Vec<&Proof> = match self.proof_result {
+			AnyValid(ref v) => v.iter().collect(),
+			BothValid(ref a, ref b) => vec![a, b],
+			_ => vec![],
+		};
+		for p in inner {
+			out.push_str(&format!("\t{} -> {} [label={}];\n", id, *next, dot_quoted(self.branch_rule.unwrap_or("?"))));
+			p.dot_at(out, next, opts);
+		}
+	}
+
+	/// The proof as a derivation for the LaTeX package bussproofs, in a `prooftree` environment.
+	/// Valid leaves are closed by an axiom, the others are marked by a symbol above them:
+	/// × for invalid, ↺ for a loop and ⋮ for skipped.
+	pub fn to_latex(&self) -> String {
+		let mut out = "\\begin{prooftree}\n".to_owned();
+		self.latex_at(&mut out);
+		out.push_str("\\end{prooftree}\n");
+		out
+	}
+
+	// Writes the derivation of this proof's start sequent, premises first as bussproofs expects.
+	fn latex_at(&self, out: &mut String) {
+		use self::ProofResult::*;
+		let opts = RenderOptions::new(Notation::Latex);
+		let sequent = |s: &Sequent| format!("${}$", s.display(&opts));
+		let last = self.steps.last().map(|x| &x.sequent).unwrap_or(&self.start);
+		let mark = |out: &mut String, symbol: &str| {
+			out.push_str(&format!("\\AxiomC{{{}}}\n\\noLine\n\\UnaryInfC{{{}}}\n", symbol, sequent(last)));
+		};
+		match self.proof_result {
+			Valid => out.push_str(&format!("\\AxiomC{{}}\n\\RightLabel{{\\scriptsize ax}}\n\\UnaryInfC{{{}}}\n", sequent(last))),
+			Invalid => mark(out, "$\\times$"),
+			Loop(_) => mark(out, "$\\circlearrowleft$"),
+			Skipped => mark(out, "$\\vdots$"),
+			AnyValid(ref v) => {
+				// of a valid proof, only the valid inner proof is of interest
+				let inner = match v.iter().find(|x| x.valid) {
+					Some(p) => vec![p],
+					None => v.iter().collect(),
+				};
+				Proof::latex_inference(out, &inner, self.branch_rule.unwrap_or("?"), &sequent(last));
 			},
 			BothValid(ref a, ref b) => {
-				a.print(depth+1);
-				b.print(depth+1);
+				Proof::latex_inference(out, &[a, b], self.branch_rule.unwrap_or("?"), &sequent(last));
 			},
-		} 
+		}
+		for i in (0..self.steps.len()).rev() {
+			let conclusion = if i == 0 {&self.start} else {&self.steps[i-1].sequent};
+			out.push_str(&format!("{}\\UnaryInfC{{{}}}\n", Proof::latex_label(self.steps[i].rule), sequent(conclusion)));
+		}
+	}
+
+	fn latex_label(rule: &str) -> String {
+		format!("\\RightLabel{{\\scriptsize {}}}\n", rule.replace('_', "\\_"))
+	}
+
+	// bussproofs allows up to five premises, so any more are elided.
+	fn latex_inference(out: &mut String, premises: &[&Proof], rule: &str, conclusion: &str) {
+		let n = premises.len().min(5);
+		for (i, p) in premises.iter().take(n).enumerate() {
+			if i == 4 && premises.len() > 5 {
+				out.push_str("\\AxiomC{$\\cdots$}\n");
+			} else {
+				p.latex_at(out);
+			}
+		}
+		let inference = ["UnaryInfC", "BinaryInfC", "TrinaryInfC", "QuaternaryInfC", "QuinaryInfC"][n-1];
+		out.push_str(&format!("{}\\{}{{{}}}\n", Proof::latex_label(rule), inference, conclusion));
+	}
+
+	/// The number of rules applied in the proof, inner proofs included.
+	pub fn size(&self) -> usize {
+		use self::ProofResult::*;
+		let inner = match self.proof_result {
+			AnyValid(ref proofs) => proofs.iter().map(|x| x.size()).sum(),
+			BothValid(ref proof_a, ref proof_b) => proof_a.size() + proof_b.size(),
+			Valid | Invalid | Loop(_) | Skipped => 0,
+		};
+		self.steps.len() + self.branch_rule.map_or(0, |_| 1) + inner
 	}
 
 	pub fn min_depth(&self) -> usize {
@@ -109,6 +374,8 @@ impl Proof {
 		match self.proof_result {
 			Valid => 0,
 			Invalid => 0,
+			Loop(_) => 0,
+			Skipped => 0,
 			AnyValid(ref proofs) => {
 				proofs.iter()
 				.map(|x| x.min_depth())
@@ -122,3 +389,84 @@ impl Proof {
 		}
 	}
 }
+
+/// Renders the proof as an indented tree of steps, one per line.
+/// Inner proofs are indented one level deeper than the proof that needs them.
+impl Render for Proof {
+	fn render(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result {
+		self.render_at(f, opts, 0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::parsing::parse_sequent;
+
+	fn proof(input: &str) -> Proof {
+		Proof::new(parse_sequent(input).unwrap(), Logic::K)
+	}
+
+	#[test]
+	fn latex_derivations_nest_premises_first() {
+		assert_eq!(proof("pVq, rVs => r, s").to_latex(), "\
+			\\begin{prooftree}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$p,r  \\Rightarrow  r,s$}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$p,s  \\Rightarrow  r,s$}\n\
+			\\RightLabel{\\scriptsize l\\_or}\n\\BinaryInfC{$r \\lor s,p  \\Rightarrow  r,s$}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$q,r  \\Rightarrow  r,s$}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$q,s  \\Rightarrow  r,s$}\n\
+			\\RightLabel{\\scriptsize l\\_or}\n\\BinaryInfC{$r \\lor s,q  \\Rightarrow  r,s$}\n\
+			\\RightLabel{\\scriptsize l\\_or}\n\\BinaryInfC{$p \\lor q,r \\lor s  \\Rightarrow  r,s$}\n\
+			\\end{prooftree}\n\
+		");
+	}
+
+	#[test]
+	fn latex_steps_are_unary_inferences_below_the_branches() {
+		assert_eq!(proof("pVq => qVp").to_latex(), "\
+			\\begin{prooftree}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$p  \\Rightarrow  q,p$}\n\
+			\\AxiomC{}\n\\RightLabel{\\scriptsize ax}\n\\UnaryInfC{$q  \\Rightarrow  q,p$}\n\
+			\\RightLabel{\\scriptsize l\\_or}\n\\BinaryInfC{$p \\lor q  \\Rightarrow  q,p$}\n\
+			\\RightLabel{\\scriptsize r\\_or}\n\\UnaryInfC{$p \\lor q  \\Rightarrow  q \\lor p$}\n\
+			\\end{prooftree}\n\
+		");
+	}
+
+	#[test]
+	fn latex_inferences_take_up_to_five_premises() {
+		let invalid = |x: &str| format!("\\AxiomC{{$\\times$}}\n\\noLine\n\\UnaryInfC{{${}  \\Rightarrow  $}}\n", x);
+		let four = proof("<>p, <>q, <>r, <>s =>").to_latex();
+		assert_eq!(four, format!(
+			"\\begin{{prooftree}}\n{}{}{}{}\\RightLabel{{\\scriptsize diam}}\n\\QuaternaryInfC{{{}}}\n\\end{{prooftree}}\n",
+			invalid("p"), invalid("q"), invalid("r"), invalid("s"),
+			"$\\Diamond p,\\Diamond q,\\Diamond r,\\Diamond s  \\Rightarrow  $",
+		));
+		// the fifth premise stands for the sixth too
+		let six = proof("<>p, <>q, <>r, <>s, <>t, <>u =>").to_latex();
+		assert!(six.contains(&format!("{}\\AxiomC{{$\\cdots$}}\n\\RightLabel{{\\scriptsize diam}}\n\\QuinaryInfC{{", invalid("s"))), "{}", six);
+		assert!(!six.contains(&invalid("t")), "{}", six);
+	}
+
+	#[test]
+	fn dot_graphs_have_a_node_per_proof() {
+		assert_eq!(proof("<>p => []p").to_dot(&RenderOptions::default()), "\
+			digraph proof {\n\
+			\tnode [shape=box];\n\
+			\t0 [label=\"<>p  =>  []p\\nvalid if any... (invalid)\"];\n\
+			\t0 -> 1 [label=\"diam\"];\n\
+			\t1 [label=\"p  =>  \\ninvalid!\"];\n\
+			\t0 -> 2 [label=\"diam\"];\n\
+			\t2 [label=\"  =>  p\\ninvalid!\"];\n\
+			}\n\
+		");
+	}
+
+	#[test]
+	fn dot_labels_are_escaped() {
+		let dot = proof("p => <>p").to_dot(&RenderOptions::new(Notation::Latex));
+		assert_eq!(dot, "digraph proof {\n\tnode [shape=box];\n\t0 [label=\"p  \\\\Rightarrow  \\\\Diamond p\\ninvalid!\"];\n}\n");
+		assert_eq!(dot_quoted("say \"hi\"\\\n"), "\"say \\\"hi\\\"\\\\\\n\"");
+	}
+}
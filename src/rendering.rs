@@ -0,0 +1,185 @@
+use std::fmt;
+
+use ::formulae::Agent;
+
+/// The set of symbols used to write out formulae and sequents.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Notation {
+	Ascii,
+	Unicode,
+	Latex,
+}
+
+impl Notation {
+	pub fn top(&self) -> &'static str {
+		match *self {
+			Notation::Latex => "\\top",
+			_ => "T",
+		}
+	}
+
+	pub fn bottom(&self) -> &'static str {
+		match *self {
+			Notation::Latex => "\\bot",
+			_ => "F",
+		}
+	}
+
+	pub fn negation(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "-",
+			Notation::Unicode => "¬",
+			Notation::Latex => "\\neg ",
+		}
+	}
+
+	pub fn conjunction(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "&",
+			Notation::Unicode => "∧",
+			Notation::Latex => " \\land ",
+		}
+	}
+
+	pub fn disjunction(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "V",
+			Notation::Unicode => "∨",
+			Notation::Latex => " \\lor ",
+		}
+	}
+
+	/// The diamond of the given agent, written `<a>` outside of LaTeX, or the plain ◇ if there is none.
+	pub fn diamond(&self, agent: Agent) -> String {
+		match (*self, agent) {
+			(Notation::Latex, Some(a)) => format!("\\Diamond_{{{}}} ", self.variable(a.as_str())),
+			(_, Some(a)) => format!("<{}>", a),
+			(Notation::Ascii, None) => "<>".to_owned(),
+			(Notation::Unicode, None) => "◇".to_owned(),
+			(Notation::Latex, None) => "\\Diamond ".to_owned(),
+		}
+	}
+
+	/// The box of the given agent, written `[a]` outside of LaTeX, or the plain □ if there is none.
+	pub fn mbox(&self, agent: Agent) -> String {
+		match (*self, agent) {
+			(Notation::Latex, Some(a)) => format!("\\Box_{{{}}} ", self.variable(a.as_str())),
+			(_, Some(a)) => format!("[{}]", a),
+			(Notation::Ascii, None) => "[]".to_owned(),
+			(Notation::Unicode, None) => "□".to_owned(),
+			(Notation::Latex, None) => "\\Box ".to_owned(),
+		}
+	}
+
+	pub fn implication(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "->",
+			Notation::Unicode => "→",
+			Notation::Latex => " \\rightarrow ",
+		}
+	}
+
+	pub fn biconditional(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "<->",
+			Notation::Unicode => "↔",
+			Notation::Latex => " \\leftrightarrow ",
+		}
+	}
+
+	pub fn xor(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "<+>",
+			Notation::Unicode => "⊕",
+			Notation::Latex => " \\oplus ",
+		}
+	}
+
+	pub fn nand(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "!&",
+			Notation::Unicode => "↑",
+			Notation::Latex => " \\uparrow ",
+		}
+	}
+
+	pub fn nor(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "!V",
+			Notation::Unicode => "↓",
+			Notation::Latex => " \\downarrow ",
+		}
+	}
+
+	/// The variable with the given name. In LaTeX, names longer than a letter are
+	/// set as a single word rather than as a product of variables.
+	pub fn variable(&self, name: &str) -> String {
+		match *self {
+			Notation::Latex if name.len() > 1 => format!("\\mathit{{{}}}", name.replace('_', "\\_")),
+			_ => name.to_owned(),
+		}
+	}
+
+	pub fn sequent_arrow(&self) -> &'static str {
+		match *self {
+			Notation::Ascii => "=>",
+			Notation::Unicode => "⇒",
+			Notation::Latex => "\\Rightarrow",
+		}
+	}
+}
+
+/// Everything that influences how things are written out.
+/// Passed explicitly wherever something is rendered, so that two renderings
+/// with different options can happen side by side.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct RenderOptions {
+	pub notation: Notation,
+}
+
+impl RenderOptions {
+	pub fn new(notation: Notation) -> RenderOptions {
+		RenderOptions {
+			notation,
+		}
+	}
+}
+
+impl Default for RenderOptions {
+	fn default() -> RenderOptions {
+		RenderOptions::new(Notation::Ascii)
+	}
+}
+
+/// Implemented by everything that can be written out under some `RenderOptions`.
+pub trait Render {
+	fn render(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result;
+}
+
+/// Adaptor pairing a value with the options to `Display` it with.
+/// Usually obtained by calling `display` on the value.
+pub struct Rendered<'a, T: 'a + ?Sized> {
+	item: &'a T,
+	opts: &'a RenderOptions,
+}
+
+impl<'a, T: 'a + ?Sized> Rendered<'a, T> {
+	pub fn new(item: &'a T, opts: &'a RenderOptions) -> Rendered<'a, T> {
+		Rendered {
+			item,
+			opts,
+		}
+	}
+}
+
+impl<'a, T: 'a + Render + ?Sized> fmt::Display for Rendered<'a, T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.item.render(f, self.opts)
+	}
+}
+
+/// Quotes `s` for use as an ID or label in Graphviz's DOT language.
+/// Line breaks become the `\n` escape, which DOT centres the lines at.
+pub fn dot_quoted(s: &str) -> String {
+	format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
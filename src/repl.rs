@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::path::PathBuf;
+
+use sequents::{parse, parse_sequent, Formula, Notation, ParseError, RenderOptions, Sequent, Symbol};
+
+use ::editing;
+use ::{report_error, show_proof, with_premises, Options};
+
+const HELP: &str = "\
+Type a formula to prove it, or one of the commands:
+  :logic [name]          show or change the logic, such as S4
+  :unicode [on|off]      write formulae in unicode rather than ascii
+  :proof [on|off]        show or hide the proofs
+  :model [on|off]        show or hide the counter-models
+  :preprocess [on|off]   rewrite -> and [] away before proving
+  :let name = formula    name a formula, for later formulae and sessions to use in place of a variable
+  :unlet name            forget a name
+  :defs                  list the named formulae
+  :save file             write the named formulae to a file
+  :load file             run every line of a file, such as one written by :save
+  :history               list the lines entered so far
+  !!, !n                 run the last line again, or line n of the history
+  :help                  show this help
+  :quit                  leave, as does the end of the input";
+
+struct Repl {
+	opts: Options,
+	// the named formulae, with the names they use already replaced
+	definitions: BTreeMap<Symbol, Formula>,
+	history: Vec<String>,
+	// the files being run by :load, so that none of them loads itself over and over
+	loading: Vec<PathBuf>,
+	// the file the named formulae are kept in from one session to the next, if any
+	definitions_file: Option<PathBuf>,
+}
+
+// The file named by SEQUENTS_DEFINITIONS, or else .sequents_definitions in the home directory.
+// Setting SEQUENTS_DEFINITIONS to nothing keeps the named formulae to the session.
+fn definitions_file() -> Option<PathBuf> {
+	match env::var_os("SEQUENTS_DEFINITIONS") {
+		Some(x) if x.is_empty() => None,
+		Some(x) => Some(PathBuf::from(x)),
+		None => env::var_os("HOME").map(|x| PathBuf::from(x).join(".sequents_definitions")),
+	}
+}
+
+/// Proves the formulae and runs the commands typed on standard input, one per line,
+/// until the input ends or the user quits.
+pub fn run(opts: Options) {
+	let stdin = io::stdin();
+	// prompts and line editing would only clutter piped input or output
+	let editor = if stdin.is_terminal() && io::stdout().is_terminal() {Some(editing::Editor::new())} else {None};
+	let mut repl = Repl::new(opts, definitions_file());
+	if let Err(e) = repl.restore() {
+		println!("{}", e);
+	}
+	if editor.is_some() {
+		println!("Type a formula to prove it, :help for the commands or :quit to leave.");
+	}
+	loop {
+		let line = if let Some(ref editor) = editor {
+			editor.read_line(&format!("{}> ", repl.opts.logic), &repl.history)
+		} else {
+			let mut line = String::new();
+			stdin.lock().read_line(&mut line).map(|n| if n == 0 {None} else {Some(line)})
+		};
+		let line = match line {
+			Ok(Some(line)) => line,
+			Ok(None) => return,
+			Err(e) => return println!("Failed to read the input: {}", e),
+		};
+		let mut line = line.trim().to_owned();
+		if line.starts_with('!') {
+			match repl.recall(&line) {
+				Ok(x) => {
+					println!("{}", x);
+					line = x;
+				},
+				Err(e) => {
+					println!("{}", e);
+					continue;
+				},
+			}
+		}
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		repl.history.push(line.clone());
+		if !repl.execute(&line) {
+			return;
+		}
+	}
+}
+
+// Reads `on` or `off`, or flips `current` if neither is given.
+fn switch(arg: &str, current: bool) -> Result<bool, String> {
+	match arg {
+		"" => Ok(!current),
+		"on" => Ok(true),
+		"off" => Ok(false),
+		_ => Err(format!("expected on or off, found '{}'", arg)),
+	}
+}
+
+fn on_off(x: bool) -> &'static str {
+	if x {"on"} else {"off"}
+}
+
+impl Repl {
+	fn new(opts: Options, definitions_file: Option<PathBuf>) -> Repl {
+		Repl {
+			opts,
+			definitions: BTreeMap::new(),
+			history: vec![],
+			loading: vec![],
+			definitions_file,
+		}
+	}
+
+	// Runs a line of input, and tells whether to carry on.
+	fn execute(&mut self, line: &str) -> bool {
+		let command = match line.strip_prefix(':') {
+			Some(command) => command,
+			None => {
+				self.prove(line);
+				return true;
+			},
+		};
+		let (name, arg) = match command.find(char::is_whitespace) {
+			Some(at) => (&command[..at], command[at..].trim()),
+			None => (command, ""),
+		};
+		let result = match name {
+			"help" => {
+				println!("{}", HELP);
+				Ok(())
+			},
+			"quit" | "q" => return false,
+			"logic" => self.logic(arg),
+			"unicode" => switch(arg, self.opts.render.notation == Notation::Unicode).map(|x| {
+				self.opts.render.notation = if x {Notation::Unicode} else {Notation::Ascii};
+				println!("unicode {}", on_off(x));
+			}),
+			"proof" => switch(arg, self.opts.show_proof).map(|x| {
+				self.opts.show_proof = x;
+				println!("proofs {}", on_off(x));
+			}),
+			"model" => switch(arg, self.opts.show_model).map(|x| {
+				self.opts.show_model = x;
+				println!("counter-models {}", on_off(x));
+			}),
+			"preprocess" => switch(arg, self.opts.preprocess).map(|x| {
+				self.opts.preprocess = x;
+				println!("preprocessing {}", on_off(x));
+			}),
+			"let" => self.define(arg).and_then(|_| self.keep()),
+			"unlet" => self.definitions.remove(&Symbol::intern(arg))
+			.ok_or_else(|| format!("'{}' names no formula", arg))
+			.and_then(|_| self.keep()),
+			"defs" => {
+				if self.definitions.is_empty() {
+					println!("no formulae are named yet, see :let");
+				}
+				for (name, f) in self.definitions.iter() {
+					println!("{} = {}", name, f.display(&self.opts.render));
+				}
+				Ok(())
+			},
+			"save" => self.save(&PathBuf::from(arg)),
+			"load" => match self.load(arg) {
+				Ok(false) => return false,
+				x => x.map(|_| ()),
+			},
+			"history" => {
+				for (i, line) in self.history.iter().enumerate() {
+					println!("{:>4}  {}", i + 1, line);
+				}
+				Ok(())
+			},
+			_ => Err(format!("unknown command ':{}', see :help for the commands", name)),
+		};
+		if let Err(e) = result {
+			println!("{}", e);
+		}
+		true
+	}
+
+	// The line of the history that `!!` or `!n` refers to.
+	fn recall(&self, line: &str) -> Result<String, String> {
+		let n = match &line[1..] {
+			"!" => self.history.len(),
+			n => n.parse::<usize>().map_err(|_| format!("expected !! or !n for a number n, found '{}'", line))?,
+		};
+		match n.checked_sub(1).and_then(|i| self.history.get(i)) {
+			Some(x) => Ok(x.clone()),
+			None => Err(format!("there is no line {} in the history, see :history", n)),
+		}
+	}
+
+	fn logic(&mut self, arg: &str) -> Result<(), String> {
+		if !arg.is_empty() {
+			self.opts.logic = arg.parse()?;
+		}
+		println!("{}, the logic of {} frames", self.opts.logic, self.opts.logic.frame_conditions());
+		Ok(())
+	}
+
+	fn define(&mut self, arg: &str) -> Result<(), String> {
+		let at = arg.find('=').ok_or("expected :let name = formula")?;
+		let name = arg[..at].trim();
+		if !Symbol::is_valid_name(name) {
+			return Err(format!("'{}' can't be a name, as it isn't spelled like a variable", name));
+		}
+		let body = arg[at+1..].trim();
+		let f = parse(body).map_err(|e| format!("Failed to parse the formula: {}", e.render(body)))?;
+		let f = self.expand(f);
+		self.definitions.insert(Symbol::intern(name), f);
+		Ok(())
+	}
+
+	// Runs the lines of a file, and tells whether to carry on.
+	fn load(&mut self, file: &str) -> Result<bool, String> {
+		let text = fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+		let path = fs::canonicalize(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+		if self.loading.contains(&path) {
+			return Err(format!("{} is already being loaded, and loading it again would never end", file));
+		}
+		self.loading.push(path);
+		let mut carry_on = true;
+		for line in text.lines().map(str::trim).filter(|x| !x.is_empty() && !x.starts_with('#')) {
+			carry_on = self.execute(line);
+			if !carry_on {
+				break;
+			}
+		}
+		self.loading.pop();
+		Ok(carry_on)
+	}
+
+	// Writes the named formulae as the commands that name them, for :load to read back.
+	fn save(&self, file: &PathBuf) -> Result<(), String> {
+		let ascii = RenderOptions::default();
+		let text = self.definitions.iter()
+		.map(|(name, f)| format!(":let {} = {}\n", name, f.display(&ascii)))
+		.collect::<String>();
+		fs::write(file, text).map_err(|e| format!("Failed to write {}: {}", file.display(), e))
+	}
+
+	// Saves the named formulae for the sessions to come, if they are kept at all.
+	fn keep(&self) -> Result<(), String> {
+		match self.definitions_file {
+			Some(ref file) => self.save(file),
+			None => Ok(()),
+		}
+	}
+
+	// Names the formulae the earlier sessions named, as they were last saved.
+	fn restore(&mut self) -> Result<(), String> {
+		let file = match self.definitions_file {
+			Some(ref file) if file.exists() => file.clone(),
+			_ => return Ok(()),
+		};
+		let text = fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+		for line in text.lines().filter(|x| !x.trim().is_empty()) {
+			let arg = line.strip_prefix(":let ").ok_or_else(|| format!("Failed to read {}: expected :let lines, found '{}'", file.display(), line))?;
+			self.define(arg).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+		}
+		Ok(())
+	}
+
+	// Proves a formula as the command line would, but carries on whatever the outcome.
+	fn prove(&self, input: &str) {
+		match self.parse(input) {
+			Ok(s) => show_proof(&self.opts, &s),
+			Err(e) => report_error(&self.opts, &format!("Failed to parse the formula: {}", e.render(input)), Some(&e)),
+		};
+	}
+
+	// Parses a formula or a sequent, replacing the names of formulae by the formulae they name.
+	fn parse(&self, input: &str) -> Result<Sequent, ParseError> {
+		let s = parse_sequent(input)?;
+		let expand = |v: &[Formula]| v.iter().map(|x| self.expand(x.clone())).collect();
+		Ok(with_premises(&self.opts, Sequent::new(expand(s.left()), expand(s.right()))))
+	}
+
+	// Replaces the names of formulae by the formulae they name.
+	fn expand(&self, f: Formula) -> Formula {
+		f.substitute(&|x| self.definitions.get(&x).cloned())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::input;
+
+	fn repl(definitions_file: Option<PathBuf>) -> Repl {
+		let opts = input(vec!["repl".to_owned()].into_iter()).unwrap();
+		Repl::new(opts, definitions_file)
+	}
+
+	// A file of its own in the temporary directory, for a test to write.
+	fn temp_file(name: &str) -> PathBuf {
+		env::temp_dir().join(format!("sequents-{}-{}", std::process::id(), name))
+	}
+
+	#[test]
+	fn names_stand_for_their_formulae() {
+		let mut r = repl(None);
+		assert!(r.execute(":let k = [](p->q) -> []p -> []q"));
+		assert!(r.execute(":let t = k & ([]p -> p)"));
+		assert_eq!(r.definitions[&Symbol::intern("t")], parse("([](p->q) -> []p -> []q) & ([]p -> p)").unwrap());
+		let s = r.parse("k, t => t & -q").unwrap();
+		assert_eq!(s.to_string(), r.parse("[](p->q) -> []p -> []q, t => t & -q").unwrap().to_string());
+		// later definitions don't change the earlier ones, which were expanded as they were made
+		assert!(r.execute(":let k = p"));
+		assert_eq!(r.definitions[&Symbol::intern("t")], parse("([](p->q) -> []p -> []q) & ([]p -> p)").unwrap());
+		assert!(r.define("K = p").is_err());
+		assert!(r.define("k p").is_err());
+	}
+
+	#[test]
+	fn lines_are_recalled_from_the_history() {
+		let mut r = repl(None);
+		r.history = vec!["p -> p".to_owned(), ":logic S4".to_owned()];
+		assert_eq!(r.recall("!!"), Ok(":logic S4".to_owned()));
+		assert_eq!(r.recall("!1"), Ok("p -> p".to_owned()));
+		assert_eq!(r.recall("!3"), Err("there is no line 3 in the history, see :history".to_owned()));
+		assert_eq!(r.recall("!0"), Err("there is no line 0 in the history, see :history".to_owned()));
+		assert_eq!(r.recall("!x"), Err("expected !! or !n for a number n, found '!x'".to_owned()));
+		assert_eq!(repl(None).recall("!!"), Err("there is no line 0 in the history, see :history".to_owned()));
+	}
+
+	#[test]
+	fn files_loading_themselves_are_loaded_once() {
+		let file = temp_file("loads-itself");
+		fs::write(&file, format!(":let a = p & q\n:load {}\n:let b = a V r\n", file.display())).unwrap();
+		let mut r = repl(None);
+		let loaded = r.load(file.to_str().unwrap());
+		assert_eq!(loaded, Ok(true));
+		assert_eq!(r.definitions[&Symbol::intern("b")], parse("(p & q) V r").unwrap());
+		assert!(r.loading.is_empty());
+		// while it is loading, it can't be loaded again
+		r.loading.push(fs::canonicalize(&file).unwrap());
+		assert!(r.load(file.to_str().unwrap()).unwrap_err().ends_with("is already being loaded, and loading it again would never end"));
+		fs::remove_file(&file).unwrap();
+	}
+
+	#[test]
+	fn definitions_last_from_one_session_to_the_next() {
+		let file = temp_file("definitions");
+		let mut first = repl(Some(file.clone()));
+		assert!(first.execute(":let k = [](p->q) -> []p -> []q"));
+		assert!(first.execute(":let t = []p -> p"));
+		assert!(first.execute(":unlet t"));
+		let mut second = repl(Some(file.clone()));
+		assert_eq!(second.restore(), Ok(()));
+		assert_eq!(second.definitions.keys().map(|x| x.as_str()).collect::<Vec<_>>(), vec!["k"]);
+		assert_eq!(second.definitions[&Symbol::intern("k")], first.definitions[&Symbol::intern("k")]);
+		fs::write(&file, ":logic S4\n").unwrap();
+		assert!(repl(Some(file.clone())).restore().unwrap_err().contains("expected :let lines, found ':logic S4'"));
+		fs::remove_file(&file).unwrap();
+		// with no file yet, there is nothing to restore
+		assert_eq!(repl(Some(file)).restore(), Ok(()));
+	}
+}
@@ -2,50 +2,219 @@ use std::fmt;
 use ::formulae::*;
 use std::collections::HashSet;
 
-use super::UNICODE_MODE;
+use ::json::Json;
+use ::logics::FrameConditions;
+use ::rendering::{Render, RenderOptions, Rendered};
+use ::symbols::Symbol;
 
 pub enum StepResult {
 	Indeterminate(&'static str, Sequent),
-	ValidIfAny(&'static str, Vec<Sequent>, HashSet<char>),
-	ValidIfBoth(&'static str, Sequent, Sequent, HashSet<char>),
-	Valid(HashSet<char>),
-	Invalid(HashSet<char>),
+	ValidIfAny(&'static str, Vec<Sequent>, HashSet<Symbol>),
+	ValidIfBoth(&'static str, Box<Sequent>, Box<Sequent>, HashSet<Symbol>),
+	Valid(HashSet<Symbol>),
+	Invalid(HashSet<Symbol>),
 }
 
+#[derive(Clone)]
 pub struct Sequent {
 	left: Vec<Formula>,
 	right: Vec<Formula>,
+	// diamonds on the right, and boxes on the left, whose body has already been added to the same side by `refl` or `lbox`
+	reflexed: Vec<Formula>,
+	// formulae still to be decided by `cut`
+	cuts: Vec<Formula>,
+	// formulae `cut` found or put on the left
+	assumed: Vec<Formula>,
+	// formulae `cut` found or put on the right
+	denied: Vec<Formula>,
+	// diamonds on the left and boxes on the right carried over from the accessing world, which are witnessed elsewhere
+	inherited: Vec<Formula>,
+	// premises holding in every world, so put on the left of every accessible world too
+	global: Vec<Formula>,
+	// the agent by whose accessibility relation the accessing world reaches this one
+	agent: Agent,
 }
 
-impl fmt::Debug for Sequent {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Render for Sequent {
+	fn render(&self, f: &mut fmt::Formatter, opts: &RenderOptions) -> fmt::Result {
 		let mut l = String::new();
 		for x in self.left.iter() {
 			if !l.is_empty() {l.push(',');}
-			l.push_str(&format!("{:?}", x));
+			l.push_str(&format!("{}", x.display(opts)));
 		}
 		let mut r = String::new();
 		for x in self.right.iter() {
 			if !r.is_empty() {r.push(',');}
-			r.push_str(&format!("{:?}", x));
+			r.push_str(&format!("{}", x.display(opts)));
 		}
-		write!(f, "{}  {}  {}", &l, if unsafe{UNICODE_MODE} {"⇒"} else {"=>"}, &r)
-    }
+		write!(f, "{}  {}  {}", &l, opts.notation.sequent_arrow(), &r)
+	}
+}
+
+impl fmt::Display for Sequent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.render(f, &RenderOptions::default())
+	}
+}
+
+impl fmt::Debug for Sequent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, f)
+	}
 }
 
 
 impl Sequent {
 	pub fn new(left: Vec<Formula>, right: Vec<Formula>) -> Sequent {
 		Sequent {
-			left: left,
-			right: right,
+			left,
+			right,
+			reflexed: vec![],
+			cuts: vec![],
+			assumed: vec![],
+			denied: vec![],
+			inherited: vec![],
+			global: vec![],
+			agent: None,
 		}
 	}
 
-	fn letters_on_left(&self) -> HashSet<char> {
+	/// The sequent with `premises` holding in every world: they go on the left of this sequent,
+	/// and of every sequent the proof creates for an accessible world.
+	pub fn with_global_premises(mut self, premises: Vec<Formula>) -> Sequent {
+		for f in premises.iter() {
+			let at = self.left.len();
+			Sequent::insert_unique(&mut self.left, at, f.clone());
+		}
+		self.global = premises;
+		self
+	}
+
+	/// The formula that holds in exactly the worlds where the sequent does: the conjunction
+	/// of the left implying the disjunction of the right, or just the latter if the left is empty.
+	/// In particular, the sequent `⇒ φ` gives φ itself.
+	pub fn as_formula(&self) -> Formula {
+		let join = |v: &[Formula], unit: Formula, op: fn(Box<Formula>, Box<Formula>) -> Formula| {
+			v.iter().cloned().reduce(|a, b| op(Box::new(a), Box::new(b))).unwrap_or(unit)
+		};
+		let right = join(&self.right, Formula::Bottom, Formula::Disjunction);
+		if self.left.is_empty() {
+			right
+		} else {
+			Formula::Implication(Box::new(join(&self.left, Formula::Top, Formula::Conjunction)), Box::new(right))
+		}
+	}
+
+	/// Prepares the sequent for a `cut` on every ◇φ and □φ occurring in it.
+	/// Once every one is on one side or the other, all worlds of a single cluster agree on them.
+	/// Unless the frames are reflexive, those nested within a ◇ or □ of another agent hold in
+	/// the worlds that agent accesses, which are left to decide them when they are created.
+	pub fn cut_modal(&mut self, conditions: FrameConditions) {
+		let mut cuts = vec![];
+		for f in self.left.iter().chain(self.right.iter()) {
+			if conditions.reflexive {
+				// every world accesses itself by every agent, so they may all hold in this one
+				f.collect_modal(&mut cuts);
+			} else {
+				f.collect_modal_within(None, &mut cuts);
+			}
+		}
+		self.cuts = cuts;
+	}
+
+	/// Prepares the sequent for a `cut` on φ for every ◇φ or □φ nested within another ◇ or □,
+	/// or occurring in a global premise.
+	/// In symmetric frames, the worlds accessible from here constrain these formulae in this world.
+	pub fn cut_bodies(&mut self) {
+		let mut modal = vec![];
+		for f in self.left.iter().chain(self.right.iter()) {
+			f.collect_modal(&mut modal);
+		}
+		let mut nested = vec![];
+		for m in modal.iter().filter_map(Formula::modal_body) {
+			m.collect_modal(&mut nested);
+		}
+		// the global premises are in every accessible world, diamonds, boxes and all
+		for g in self.global.iter() {
+			g.collect_modal(&mut nested);
+		}
+		let mut cuts = vec![];
+		for x in nested.iter().filter_map(Formula::modal_body) {
+			if !cuts.contains(x) {
+				cuts.push(x.clone());
+			}
+		}
+		self.cuts = cuts;
+	}
+
+	// A sequent with the given formulae, and everything else carried over from this one.
+	fn with_formulae(&self, left: Vec<Formula>, right: Vec<Formula>) -> Sequent {
+		Sequent {
+			left,
+			right,
+			reflexed: self.reflexed.clone(),
+			cuts: self.cuts.clone(),
+			assumed: self.assumed.clone(),
+			denied: self.denied.clone(),
+			inherited: self.inherited.clone(),
+			global: self.global.clone(),
+			agent: self.agent,
+		}
+	}
+
+	pub fn display<'a>(&'a self, opts: &'a RenderOptions) -> Rendered<'a, Sequent> {
+		Rendered::new(self, opts)
+	}
+
+	/// The sequent as a JSON object with the formulae on either side, like
+	/// `{"left": ["p"], "right": ["q", "<>r"]}`.
+	pub fn to_json(&self, opts: &RenderOptions) -> Json {
+		let side = |v: &[Formula]| Json::Array(v.iter().map(|x| Json::String(x.display(opts).to_string())).collect());
+		Json::Object(vec![
+			("left".to_owned(), side(&self.left)),
+			("right".to_owned(), side(&self.right)),
+		])
+	}
+
+	pub fn left(&self) -> &[Formula] {
+		&self.left
+	}
+
+	pub fn right(&self) -> &[Formula] {
+		&self.right
+	}
+
+	pub fn global_premises(&self) -> &[Formula] {
+		&self.global
+	}
+
+	/// The agent by whose accessibility relation the world of this sequent is reached,
+	/// `None` for the plain one, which also stands for the world the proof starts in.
+	pub fn agent(&self) -> Agent {
+		self.agent
+	}
+
+	/// Whether both sequents have the same formulae on either side, regardless of order and repetition.
+	pub fn same_formulae(&self, other: &Sequent) -> bool {
+		fn same_set(a: &[Formula], b: &[Formula]) -> bool {
+			a.iter().all(|x| b.contains(x)) && b.iter().all(|x| a.contains(x))
+		}
+		same_set(&self.left, &other.left) && same_set(&self.right, &other.right)
+	}
+
+	// Sequents are sets of formulae on either side, so a formula already there isn't added again.
+	fn insert_unique(v: &mut Vec<Formula>, i: usize, f: Formula) -> usize {
+		if v.contains(&f) {
+			return i;
+		}
+		v.insert(i, f);
+		i + 1
+	}
+
+	fn letters_on_left(&self) -> HashSet<Symbol> {
 		let mut s = HashSet::new();
 		for f in self.left.iter() {
-			if let &Formula::Letter(x) = f {
+			if let Formula::Letter(x) = *f {
 				s.insert(x);
 			}
 		}
@@ -56,30 +225,30 @@ impl Sequent {
 		// if self.right.len() == 0 {
 		// 	return true;
 		// }
-		let mut lefts: Vec<char> = vec![];
+		let mut lefts: Vec<Symbol> = vec![];
 		for r in self.right.iter() {
 			if r == &Formula::Top {
 				return true
 			}
 		}
 		for l in self.left.iter() {
-			if let &Formula::Letter(x) = l {
+			if let Formula::Letter(x) = *l {
 				lefts.push(x);
 			} else if &Formula::Bottom == l {
 				return true;
 			}
 		}
-		if lefts.len() == 0 {
-			return false;
-		}
-		for r in self.right.iter() {
-			if let &Formula::Letter(x) = r {
-				if lefts.contains(&x) {
-					return true;
+		if !lefts.is_empty() {
+			for r in self.right.iter() {
+				if let Formula::Letter(x) = *r {
+					if lefts.contains(&x) {
+						return true;
+					}
 				}
 			}
 		}
-		false
+		// the diamonds and boxes decided by `cut` may turn up on both sides without being decomposed
+		self.left.iter().any(|l| l.modal_body().is_some() && self.right.contains(l))
 	}
 
 	pub fn try_ltop(&mut self) -> bool {
@@ -104,9 +273,9 @@ impl Sequent {
 
 	//TODO check for bottoms on left
 	
-	// Attempts to take one step. returns Some(x) when successful where x is the rule applied
-	pub fn step(mut self) -> StepResult {
-		use StepResult::*;
+	// Attempts to take one step under the rules of the given logic.
+	pub fn step(mut self, conditions: FrameConditions) -> StepResult {
+		use self::StepResult::*;
 		if self.certainly_valid() {
 			return Valid(self.letters_on_left());
 		}
@@ -116,17 +285,59 @@ impl Sequent {
 		if self.try_rneg() {return Indeterminate("rneg", self);}
 		if self.try_land() {return Indeterminate("land", self);}
 		if self.try_r_or() {return Indeterminate("r_or", self);}
+		if self.try_rimp() {return Indeterminate("rimp", self);}
+		if self.try_rnand() {return Indeterminate("rnand", self);}
+		if self.try_lnor() {return Indeterminate("lnor", self);}
 		if let Some((a, b)) = self.try_l_or() {
-			return ValidIfBoth("l_or", a, b, self.letters_on_left());
+			return ValidIfBoth("l_or", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_limp() {
+			return ValidIfBoth("limp", Box::new(a), Box::new(b), self.letters_on_left());
 		}
 		if let Some((a, b)) = self.try_rand() {
-			return ValidIfBoth("rand", a, b, self.letters_on_left());
+			return ValidIfBoth("rand", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_liff() {
+			return ValidIfBoth("liff", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_riff() {
+			return ValidIfBoth("riff", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_lxor() {
+			return ValidIfBoth("lxor", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_rxor() {
+			return ValidIfBoth("rxor", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if let Some((a, b)) = self.try_lnand() {
+			return ValidIfBoth("lnand", Box::new(a), Box::new(b), self.letters_on_left());
 		}
+		if let Some((a, b)) = self.try_rnor() {
+			return ValidIfBoth("rnor", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+
+		if let Some((a, b)) = self.try_cut(conditions) {
+			return ValidIfBoth("cut", Box::new(a), Box::new(b), self.letters_on_left());
+		}
+		if conditions.reflexive && self.try_refl() {return Indeterminate("refl", self);}
+		if conditions.reflexive && self.try_lbox() {return Indeterminate("lbox", self);}
 
 		//TODO rules 5, 6
-		let diam = self.try_diam();
-		if !diam.is_empty() {
-			return ValidIfAny("diam", diam, self.letters_on_left());
+		let mut worlds = self.try_diam(conditions);
+		// named after the diamonds on the left, unless only boxes on the right, or only `serial`, call for worlds
+		let rule = if self.left.iter().any(|x| x.get_type() == FormulaType::MDiamond && !self.inherited.contains(x)) {
+			"diam"
+		} else if !worlds.is_empty() {
+			"rbox"
+		} else {
+			"serial"
+		};
+		// in reflexive frames every world is its own accessible world, which `refl` already accounts for
+		if conditions.serial && !conditions.reflexive {
+			worlds.extend(self.try_serial(conditions));
+		}
+		if !worlds.is_empty() {
+			return ValidIfAny(rule, worlds, self.letters_on_left());
 		}
 		Invalid(self.letters_on_left())
 	}
@@ -136,7 +347,8 @@ impl Sequent {
 			if let Formula::Negation(_) = self.left[i] {
 				let n = self.left.remove(i);
 				if let Formula::Negation(x) = n {
-					self.right.push(*x);
+					let at = self.right.len();
+					Sequent::insert_unique(&mut self.right, at, *x);
 				} else {panic!()}
 				return true;
 			}
@@ -149,7 +361,8 @@ impl Sequent {
 			if let Formula::Negation(_) = self.right[i] {
 				let n = self.right.remove(i);
 				if let Formula::Negation(x) = n {
-					self.left.push(*x);
+					let at = self.left.len();
+					Sequent::insert_unique(&mut self.left, at, *x);
 				} else {panic!()}
 				return true;
 			}
@@ -162,8 +375,8 @@ impl Sequent {
 			if let Formula::Conjunction(_,_) = self.left[i] {
 				let n = self.left.remove(i);
 				if let Formula::Conjunction(x, y) = n {
-					self.left.insert(i, *x);
-					self.left.insert(i+1, *y);
+					let at = Sequent::insert_unique(&mut self.left, i, *x);
+					Sequent::insert_unique(&mut self.left, at, *y);
 				} else {panic!()}
 				return true;
 			}
@@ -178,8 +391,24 @@ impl Sequent {
 			if let Formula::Disjunction(_,_) = self.right[i] {
 				let n = self.right.remove(i);
 				if let Formula::Disjunction(x, y) = n {
-					self.right.insert(i, *x);
-					self.right.insert(i+1, *y);
+					let at = Sequent::insert_unique(&mut self.right, i, *x);
+					Sequent::insert_unique(&mut self.right, at, *y);
+				} else {panic!()}
+				return true;
+			}
+		}
+		false
+	}
+
+	// A world falsifying φ→ψ satisfies φ and falsifies ψ.
+	pub fn try_rimp(&mut self) -> bool {
+		for i in 0..self.right.len() {
+			if let Formula::Implication(_,_) = self.right[i] {
+				let n = self.right.remove(i);
+				if let Formula::Implication(x, y) = n {
+					let at = self.left.len();
+					Sequent::insert_unique(&mut self.left, at, *x);
+					Sequent::insert_unique(&mut self.right, i, *y);
 				} else {panic!()}
 				return true;
 			}
@@ -189,13 +418,13 @@ impl Sequent {
 
 	pub fn try_l_or(&mut self) -> Option<(Sequent, Sequent)> {
 		for i in 0..self.left.len() {
-			if let Some(&Formula::Disjunction(ref x, ref y)) = self.left.get(i) {
-				let mut lhs = (0..i).chain(i+1..self.left.len())
+			if let Some(Formula::Disjunction(x, y)) = self.left.get(i) {
+				let lhs = (0..i).chain(i+1..self.left.len())
 				.map(|x| self.left.get(x).unwrap().clone())
 				.collect::<Vec<_>>();
 				return Some((
-					Sequent::new({let mut l = lhs.clone(); l.push((**x).clone()); l}, self.right.clone()),
-					Sequent::new({let mut l = lhs.clone(); l.push((**y).clone()); l}, self.right.clone()),
+					self.with_formulae({let mut l = lhs.clone(); Sequent::insert_unique(&mut l, lhs.len(), (**x).clone()); l}, self.right.clone()),
+					self.with_formulae({let mut l = lhs.clone(); Sequent::insert_unique(&mut l, lhs.len(), (**y).clone()); l}, self.right.clone()),
 				));
 			}
 		}
@@ -204,35 +433,505 @@ impl Sequent {
 
 	pub fn try_rand(&mut self) -> Option<(Sequent, Sequent)> {
 		for i in 0..self.right.len() {
-			if let Some(&Formula::Conjunction(ref x, ref y)) = self.right.get(i) {
-				let mut rhs = (0..i).chain(i+1..self.right.len())
+			if let Some(Formula::Conjunction(x, y)) = self.right.get(i) {
+				let rhs = (0..i).chain(i+1..self.right.len())
 				.map(|x| self.right.get(x).unwrap().clone())
 				.collect::<Vec<_>>();
 				return Some((
-					Sequent::new(self.left.clone(), {let mut r = rhs.clone(); r.push((**x).clone()); r}),
-					Sequent::new(self.left.clone(), {let mut r = rhs.clone(); r.push((**y).clone()); r}),
+					self.with_formulae(self.left.clone(), {let mut r = rhs.clone(); Sequent::insert_unique(&mut r, rhs.len(), (**x).clone()); r}),
+					self.with_formulae(self.left.clone(), {let mut r = rhs.clone(); Sequent::insert_unique(&mut r, rhs.len(), (**y).clone()); r}),
 				));
 			}
 		}
 		None
 	}
 
-	pub fn try_diam(&mut self) -> Vec<Sequent> {
-		let mut vec = vec![];
-		let rhs: Vec<Formula> = self.right.iter()
-		.map(|x| if let &Formula::MDiamond(ref q) = x {Some((**q).clone())} else {None})
-		.filter(|x| x.is_some())
-		.map(|x| x.unwrap())
-		.collect::<Vec<_>>();
-		for l in self.left.iter() {
-			if let &Formula::MDiamond(ref inner) = l {
-				let x: Formula = (**inner).clone();
-				vec.push(Sequent::new(
-					vec![x],
-					rhs.clone(),
-				))
+	// The sequent without the `i`th formula on the left, or on the right, and with the given formulae added to either side.
+	fn replaced(&self, on_left: bool, i: usize, add_left: &[&Formula], add_right: &[&Formula]) -> Sequent {
+		let mut left = self.left.clone();
+		let mut right = self.right.clone();
+		if on_left {
+			left.remove(i);
+		} else {
+			right.remove(i);
+		}
+		for &f in add_left {
+			let at = left.len();
+			Sequent::insert_unique(&mut left, at, f.clone());
+		}
+		for &f in add_right {
+			let at = right.len();
+			Sequent::insert_unique(&mut right, at, f.clone());
+		}
+		self.with_formulae(left, right)
+	}
+
+	// A world satisfying φ→ψ falsifies φ or satisfies ψ.
+	pub fn try_limp(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.left.len() {
+			if let Formula::Implication(ref x, ref y) = self.left[i] {
+				return Some((self.replaced(true, i, &[], &[x]), self.replaced(true, i, &[y], &[])));
 			}
 		}
-		vec
+		None
+	}
+
+	// A world satisfying φ↔ψ satisfies both or neither.
+	pub fn try_liff(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.left.len() {
+			if let Formula::Biconditional(ref x, ref y) = self.left[i] {
+				return Some((self.replaced(true, i, &[x, y], &[]), self.replaced(true, i, &[], &[x, y])));
+			}
+		}
+		None
+	}
+
+	// A world falsifying φ↔ψ satisfies one but not the other.
+	pub fn try_riff(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.right.len() {
+			if let Formula::Biconditional(ref x, ref y) = self.right[i] {
+				return Some((self.replaced(false, i, &[x], &[y]), self.replaced(false, i, &[y], &[x])));
+			}
+		}
+		None
 	}
-}
\ No newline at end of file
+
+	// A world satisfying φ⊕ψ satisfies one but not the other.
+	pub fn try_lxor(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.left.len() {
+			if let Formula::Xor(ref x, ref y) = self.left[i] {
+				return Some((self.replaced(true, i, &[x], &[y]), self.replaced(true, i, &[y], &[x])));
+			}
+		}
+		None
+	}
+
+	// A world falsifying φ⊕ψ satisfies both or neither.
+	pub fn try_rxor(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.right.len() {
+			if let Formula::Xor(ref x, ref y) = self.right[i] {
+				return Some((self.replaced(false, i, &[x, y], &[]), self.replaced(false, i, &[], &[x, y])));
+			}
+		}
+		None
+	}
+
+	// A world falsifying φ↑ψ satisfies both.
+	pub fn try_rnand(&mut self) -> bool {
+		for i in 0..self.right.len() {
+			if let Formula::Nand(ref x, ref y) = self.right[i] {
+				*self = self.replaced(false, i, &[x, y], &[]);
+				return true;
+			}
+		}
+		false
+	}
+
+	// A world satisfying φ↓ψ falsifies both.
+	pub fn try_lnor(&mut self) -> bool {
+		for i in 0..self.left.len() {
+			if let Formula::Nor(ref x, ref y) = self.left[i] {
+				*self = self.replaced(true, i, &[], &[x, y]);
+				return true;
+			}
+		}
+		false
+	}
+
+	// A world satisfying φ↑ψ falsifies one or the other.
+	pub fn try_lnand(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.left.len() {
+			if let Formula::Nand(ref x, ref y) = self.left[i] {
+				return Some((self.replaced(true, i, &[], &[x]), self.replaced(true, i, &[], &[y])));
+			}
+		}
+		None
+	}
+
+	// A world falsifying φ↓ψ satisfies one or the other.
+	pub fn try_rnor(&mut self) -> Option<(Sequent, Sequent)> {
+		for i in 0..self.right.len() {
+			if let Formula::Nor(ref x, ref y) = self.right[i] {
+				return Some((self.replaced(false, i, &[x], &[]), self.replaced(false, i, &[y], &[])));
+			}
+		}
+		None
+	}
+
+	// Whether `x` occurs in any formula of the sequent, or of the global premises.
+	// In euclidean frames that are not reflexive, one nested within a ◇ or □ of another agent
+	// is up to the worlds that agent accesses, so it doesn't count.
+	fn mentions(&self, x: &Formula, conditions: FrameConditions) -> bool {
+		self.left.iter().chain(self.right.iter()).chain(self.global.iter()).any(|f| {
+			if conditions.euclidean && !conditions.reflexive {
+				let mut modal = vec![];
+				f.collect_modal_within(None, &mut modal);
+				modal.contains(x)
+			} else {
+				f.has_subformula(x)
+			}
+		})
+	}
+
+	// Every world either satisfies ◇φ or falsifies it.
+	pub fn try_cut(&mut self, conditions: FrameConditions) -> Option<(Sequent, Sequent)> {
+		while let Some(x) = self.cuts.pop() {
+			if self.left.contains(&x) {
+				self.assumed.push(x);
+			} else if self.right.contains(&x) {
+				self.denied.push(x);
+			} else if !self.mentions(&x, conditions) {
+				// the rules that brought it up decomposed it away, or kept it in another branch,
+				// so no world this sequent stands for can be told apart by it
+				continue;
+			} else {
+				let mut r = self.right.clone();
+				r.push(x.clone());
+				let mut l = self.left.clone();
+				l.push(x.clone());
+				let mut a = self.with_formulae(self.left.clone(), r);
+				a.denied.push(x.clone());
+				let mut b = self.with_formulae(l, self.right.clone());
+				b.assumed.push(x);
+				return Some((a, b));
+			}
+		}
+		None
+	}
+
+	// A world falsifying ◇φ falsifies φ in all accessible worlds, which in reflexive frames includes itself.
+	pub fn try_refl(&mut self) -> bool {
+		for i in 0..self.right.len() {
+			if let Formula::MDiamond(_, ref x) = self.right[i] {
+				if !self.reflexed.contains(&self.right[i]) {
+					let x = (**x).clone();
+					self.reflexed.push(self.right[i].clone());
+					if !self.right.contains(&x) {
+						self.right.push(x);
+						return true;
+					}
+				}
+			}
+		}
+		false
+	}
+
+	// Likewise, a world satisfying □φ satisfies φ in all accessible worlds, itself included.
+	pub fn try_lbox(&mut self) -> bool {
+		for i in 0..self.left.len() {
+			if let Formula::MBox(_, ref x) = self.left[i] {
+				if !self.reflexed.contains(&self.left[i]) {
+					let x = (**x).clone();
+					self.reflexed.push(self.left[i].clone());
+					if !self.left.contains(&x) {
+						self.left.push(x);
+						return true;
+					}
+				}
+			}
+		}
+		false
+	}
+
+	// The formulae of the given type, such as all the ◇φ.
+	fn of_type(v: &[Formula], t: FormulaType) -> impl Iterator<Item = &Formula> {
+		v.iter().filter(move |x| x.get_type() == t)
+	}
+
+	// The formulae of the given type about the given agent, such as all the <a>φ.
+	fn of_agent(v: &[Formula], t: FormulaType, agent: Agent) -> impl Iterator<Item = &Formula> {
+		Sequent::of_type(v, t).filter(move |x| x.modal_agent() == Some(agent))
+	}
+
+	// A world accessible from this one by the given agent, which falsifies φ for every ◇φ of the agent
+	// on the right and satisfies φ for every □φ of the agent on the left. It starts with the body of `witness`,
+	// a ◇ψ on the left or a □ψ on the right, on the same side, and with the global premises on the left.
+	// In transitive frames, a world falsifying ◇φ also falsifies ◇φ in all accessible worlds,
+	// and one satisfying □φ satisfies □φ in them, so these are carried over into every accessible world.
+	fn accessible_world(&self, agent: Agent, witness: Option<&Formula>, conditions: FrameConditions) -> Sequent {
+		use self::FormulaType::{MBox, MDiamond};
+		let body = |x: &Formula| x.modal_body().unwrap().clone();
+		let mut lhs = Sequent::of_agent(&self.left, MBox, agent).map(body).collect::<Vec<_>>();
+		let mut rhs = Sequent::of_agent(&self.right, MDiamond, agent).map(body).collect::<Vec<_>>();
+		match witness {
+			Some(w) if w.get_type() == MDiamond => lhs.insert(0, body(w)),
+			Some(w) => rhs.insert(0, body(w)),
+			None => (),
+		}
+		if conditions.transitive {
+			lhs.extend(Sequent::of_agent(&self.left, MBox, agent).cloned());
+			rhs.extend(Sequent::of_agent(&self.right, MDiamond, agent).cloned());
+		}
+		// Likewise, in euclidean frames every world satisfying ◇φ has all its accessible worlds satisfy ◇φ,
+		// witnessed by the other worlds accessible from this one, and every world falsifying □φ has them falsify □φ.
+		let (mut inherited_left, mut inherited_right) = if conditions.euclidean {(
+			Sequent::of_agent(&self.left, MDiamond, agent).cloned().collect(),
+			Sequent::of_agent(&self.right, MBox, agent).cloned().collect(),
+		)} else {
+			(vec![], vec![])
+		};
+		// In symmetric frames the accessible world accesses this one, where the assumed formulae hold
+		// and the denied ones don't.
+		let symmetric = conditions.symmetric && !conditions.euclidean;
+		if symmetric {
+			inherited_left.extend(self.assumed.iter().map(|x| Formula::MDiamond(agent, Box::new(x.clone()))));
+			inherited_right.extend(self.denied.iter().map(|x| Formula::MBox(agent, Box::new(x.clone()))));
+		}
+		let mut left = vec![];
+		for f in lhs.into_iter().chain(inherited_left.iter().cloned()).chain(self.global.iter().cloned()) {
+			let at = left.len();
+			Sequent::insert_unique(&mut left, at, f);
+		}
+		let mut right = vec![];
+		for f in rhs.into_iter().chain(inherited_right.iter().cloned()) {
+			let at = right.len();
+			Sequent::insert_unique(&mut right, at, f);
+		}
+		let mut s = Sequent::new(left, right);
+		inherited_left.extend(inherited_right);
+		s.inherited = inherited_left;
+		s.global = self.global.clone();
+		s.agent = agent;
+		if symmetric {
+			s.cut_bodies();
+		} else if conditions.euclidean {
+			// the worlds it accesses by any other agent make up a cluster of their own, which has to agree on
+			// the diamonds and boxes of that agent just like the worlds this one accesses
+			s.cut_modal(conditions);
+		}
+		s
+	}
+
+	// Every ◇φ on the left, and every □φ on the right, calls for a world accessible by the same agent
+	// with φ on the same side.
+	pub fn try_diam(&mut self, conditions: FrameConditions) -> Vec<Sequent> {
+		Sequent::of_type(&self.left, FormulaType::MDiamond)
+		.chain(Sequent::of_type(&self.right, FormulaType::MBox))
+		// an inherited one is already witnessed by a world this one accesses
+		.filter(|x| !self.inherited.contains(x))
+		.map(|x| self.accessible_world(x.modal_agent().unwrap(), Some(x), conditions))
+		.collect()
+	}
+
+	// In serial frames, a world falsifying ◇φ or satisfying □φ accesses some world falsifying,
+	// or satisfying, φ, even if it has no diamonds on the left or boxes on the right of the same agent
+	// that would call for one. Gives such a world for every agent that needs one.
+	pub fn try_serial(&mut self, conditions: FrameConditions) -> Vec<Sequent> {
+		let agents_of = |a: &[Formula], at: FormulaType, b: &[Formula], bt: FormulaType| {
+			let mut agents = vec![];
+			for x in Sequent::of_type(a, at).chain(Sequent::of_type(b, bt)) {
+				let agent = x.modal_agent().unwrap();
+				if !agents.contains(&agent) {
+					agents.push(agent);
+				}
+			}
+			agents
+		};
+		let witnessed = agents_of(&self.left, FormulaType::MDiamond, &self.right, FormulaType::MBox);
+		let constrained = agents_of(&self.right, FormulaType::MDiamond, &self.left, FormulaType::MBox);
+		constrained.into_iter()
+		.filter(|x| !witnessed.contains(x))
+		.map(|x| self.accessible_world(x, None, conditions))
+		.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use ::logics::Logic;
+	use ::parsing::parse_sequent;
+	use ::proofs::{Proof, ProofResult};
+	use ::{preprocess_sequent, prove_sequent_before, prove_sequent_in, Outcome};
+	use std::time::{Duration, Instant};
+
+	// The rules used anywhere in the proof.
+	fn rules(p: &Proof, out: &mut Vec<&'static str>) {
+		out.extend(p.steps().iter().map(|x| x.rule));
+		out.extend(p.branch_rule());
+		match *p.proof_result() {
+			ProofResult::AnyValid(ref v) => v.iter().for_each(|x| rules(x, out)),
+			ProofResult::BothValid(ref a, ref b) => {
+				rules(a, out);
+				rules(b, out);
+			},
+			_ => (),
+		}
+	}
+
+	// Proves the sequent in K, checking the rule is used and that the counter-model, if any, refutes it.
+	fn valid(input: &str, rule: &str) -> bool {
+		let s = parse_sequent(input).unwrap();
+		let outcome = prove_sequent_in(&s, Logic::K);
+		let mut used = vec![];
+		rules(outcome.proof(), &mut used);
+		assert!(used.contains(&rule), "{} not used in proving {}", rule, input);
+		if let Some(m) = outcome.counter_model() {
+			assert!(!m.satisfies(1, &s.as_formula()), "{:?} doesn't refute {}", m, input);
+		}
+		outcome.valid()
+	}
+
+	// Proves the sequent, failing if that takes long enough to be a search blowing up.
+	fn prove_quickly(input: &str, logic: Logic) -> Outcome {
+		let s = parse_sequent(input).unwrap();
+		let outcome = prove_sequent_before(&s, logic, Instant::now() + Duration::from_secs(10))
+			.unwrap_or_else(|_| panic!("{} took too long in {}", input, logic));
+		if let Some(m) = outcome.counter_model() {
+			assert!(!m.satisfies(1, &s.as_formula()), "{:?} doesn't refute {}", m, input);
+		}
+		outcome
+	}
+
+	// The sequents `step` leaves to prove.
+	fn step(input: &str) -> (&'static str, Vec<String>) {
+		let show = |s: &Sequent| s.to_string();
+		match parse_sequent(input).unwrap().step(FrameConditions::default()) {
+			StepResult::Indeterminate(rule, s) => (rule, vec![show(&s)]),
+			StepResult::ValidIfBoth(rule, a, b, _) => (rule, vec![show(&a), show(&b)]),
+			_ => panic!("{} took no step", input),
+		}
+	}
+
+	#[test]
+	fn connective_rules_take_their_steps() {
+		let cases = [
+			("p<->q => r", "liff", vec!["p,q  =>  r", "  =>  r,p,q"]),
+			("r => p<->q", "riff", vec!["r,p  =>  q", "r,q  =>  p"]),
+			("p<+>q => r", "lxor", vec!["p  =>  r,q", "q  =>  r,p"]),
+			("r => p<+>q", "rxor", vec!["r,p,q  =>  ", "r  =>  p,q"]),
+			("p!&q => r", "lnand", vec!["  =>  r,p", "  =>  r,q"]),
+			("r => p!&q", "rnand", vec!["r,p,q  =>  "]),
+			("p!Vq => r", "lnor", vec!["  =>  r,p,q"]),
+			("r => p!Vq", "rnor", vec!["r,p  =>  ", "r,q  =>  "]),
+		];
+		for &(input, rule, ref rest) in cases.iter() {
+			assert_eq!(step(input), (rule, rest.iter().map(|x| x.to_string()).collect()), "{}", input);
+		}
+	}
+
+	#[test]
+	fn connective_rules_prove_and_refute() {
+		let cases = [
+			("p<->q, p => q", "liff", true),
+			("p<->q => p", "liff", false),
+			("p&q => p<->q", "riff", true),
+			("p => p<->q", "riff", false),
+			("p<+>q, p => -q", "lxor", true),
+			("p<+>q => p", "lxor", false),
+			("=> p<+>-p", "rxor", true),
+			("p => p<+>q", "rxor", false),
+			("p!&q, p => -q", "lnand", true),
+			("p!&q => -p", "lnand", false),
+			("=> p!&-p", "rnand", true),
+			("p => p!&q", "rnand", false),
+			("p!Vq => -p", "lnor", true),
+			("p!Vq => p", "lnor", false),
+			("-p, -q => p!Vq", "rnor", true),
+			("-p => p!Vq", "rnor", false),
+			("=> <>(p!&q) <-> <>-(p&q)", "riff", true),
+			("=> [](p!Vq) <+> <>(pVq)", "rxor", true),
+		];
+		for &(input, rule, expected) in cases.iter() {
+			assert_eq!(valid(input, rule), expected, "{}", input);
+		}
+	}
+
+	#[test]
+	fn connectives_agree_with_their_definitions() {
+		let cases = [
+			"(p<->q) <-> (p->q)&(q->p)",
+			"(p<+>q) <-> -(p<->q)",
+			"(p!&q) <-> -(p&q)",
+			"(p!Vq) <-> -(pVq)",
+			"(p!&p) <-> -p",
+			"(p!Vp) <-> -p",
+		];
+		for f in cases.iter() {
+			for l in Logic::ALL.iter() {
+				let s = parse_sequent(f).unwrap();
+				assert!(prove_sequent_in(&s, *l).valid(), "{} in {}", f, l);
+			}
+		}
+	}
+
+	#[test]
+	fn cuts_nothing_mentions_any_more_are_dropped() {
+		// each conjunct is an instance of the axiom 5, but only leaves its own diamonds to cut once `rand` split it off
+		let conjuncts = (0..12).map(|i| format!("(<>p{0} -> []<>p{0})", i)).collect::<Vec<_>>().join(" & ");
+		assert!(prove_quickly(&format!("=> {}", conjuncts), Logic::S5).valid());
+		assert!(!prove_quickly(&format!("=> {} & (<>q -> []q)", conjuncts), Logic::S5).valid());
+	}
+
+	#[test]
+	fn modalities_of_other_agents_are_left_to_their_worlds() {
+		// the diamonds within [a] are those of the worlds a accesses, not of the cluster this one is in
+		let conjuncts = (0..12).map(|i| format!("(<>p{0} -> []<>p{0})", i)).collect::<Vec<_>>().join(" & ");
+		for l in &[Logic::K45, Logic::KD45] {
+			assert!(prove_quickly(&format!("=> [a]({})", conjuncts), *l).valid());
+			assert!(!prove_quickly(&format!("=> [a]({} & (<>q -> []q))", conjuncts), *l).valid());
+		}
+		// but in reflexive frames, every world is one of those it accesses by any agent
+		assert!(!prove_quickly("=> []<a>([]r <-> q)", Logic::S5).valid());
+	}
+
+	#[test]
+	fn global_premises_hold_in_every_world() {
+		let p = parse_sequent("p").unwrap().as_formula();
+		let global = parse_sequent("=> []p").unwrap().with_global_premises(vec![p.clone()]);
+		assert!(prove_sequent_in(&global, Logic::K).valid());
+		// assumed only where the formula is to hold, p says nothing about the accessible worlds
+		let local = parse_sequent("p => []p").unwrap();
+		let outcome = prove_sequent_in(&local, Logic::K);
+		let m = outcome.counter_model().unwrap();
+		assert!(m.satisfies(1, &p) && !m.satisfies_everywhere(&p));
+		assert!(!m.satisfies(1, &local.as_formula()));
+		// and where the global premises don't settle it, the counter-model still satisfies them everywhere
+		let s = parse_sequent("=> []q").unwrap().with_global_premises(vec![p.clone()]);
+		let outcome = prove_sequent_in(&s, Logic::K);
+		let m = outcome.counter_model().unwrap();
+		assert!(m.satisfies_everywhere(&p) && !m.satisfies(1, &s.as_formula()));
+		assert!(m.num_worlds > 1);
+	}
+
+	#[test]
+	fn implications_and_boxes_take_their_own_rules() {
+		let s = parse_sequent("=> [](p->q) -> []p -> []q").unwrap();
+		let mut used = vec![];
+		rules(prove_sequent_in(&s, Logic::K).proof(), &mut used);
+		for rule in &["rimp", "limp", "rbox"] {
+			assert!(used.contains(rule), "{} not used in {:?}", rule, used);
+		}
+		// preprocessing leaves only negations, disjunctions and diamonds to prove
+		let mut used = vec![];
+		rules(prove_sequent_in(&preprocess_sequent(&s), Logic::K).proof(), &mut used);
+		assert!(used.iter().all(|x| !["rimp", "limp", "rbox"].contains(x)), "{:?}", used);
+		assert!(used.contains(&"diam"));
+	}
+
+	#[test]
+	fn proofs_agree_with_those_of_the_preprocessed_sequents() {
+		let cases = [
+			"=> [](p->q) -> []p -> []q",
+			"[]p => <>p",
+			"=> []p -> p",
+			"=> p -> []<>p",
+			"=> []p -> [][]p",
+			"=> <>p -> []<>p",
+			"[](p V q) => []p V []q",
+			"<>(p->q), []p => <>q",
+			"[]([]p -> p) => []p",
+			"p -> []q, q -> []p => p -> [][]p",
+			"[a]p => [b]p",
+			"=> <a>[a]p -> [a]p",
+		];
+		for input in cases.iter() {
+			let s = parse_sequent(input).unwrap();
+			for l in Logic::ALL.iter() {
+				let native = prove_quickly(input, *l);
+				let preprocessed = prove_sequent_in(&preprocess_sequent(&s), *l);
+				assert_eq!(native.valid(), preprocessed.valid(), "{} in {}", input, l);
+			}
+		}
+	}
+}
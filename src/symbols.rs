@@ -0,0 +1,126 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+
+/// The name of a propositional variable, interned so that it is cheap to copy, compare and hash.
+/// Symbols are ordered by their names.
+#[derive(Copy, Clone)]
+pub struct Symbol(&'static str);
+
+fn interner() -> &'static Mutex<HashSet<&'static str>> {
+	static INTERNER: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+	INTERNER.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+impl Symbol {
+	pub fn intern(name: &str) -> Symbol {
+		let mut names = interner().lock().unwrap();
+		if let Some(&name) = names.get(name) {
+			return Symbol(name);
+		}
+		// interned names live as long as the program does
+		let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+		names.insert(name);
+		Symbol(name)
+	}
+
+	pub fn as_str(&self) -> &'static str {
+		self.0
+	}
+
+	/// Whether `name` is spelled like a variable: a lowercase letter followed by
+	/// any number of lowercase letters, digits and underscores.
+	pub fn is_valid_name(name: &str) -> bool {
+		let mut chars = name.chars();
+		match chars.next() {
+			Some(c) if c.is_lowercase() => chars.all(Symbol::is_continuation),
+			_ => false,
+		}
+	}
+
+	pub fn is_continuation(c: char) -> bool {
+		c.is_lowercase() || c.is_ascii_digit() || c == '_'
+	}
+}
+
+// every name is interned once, so equal symbols share their address
+impl PartialEq for Symbol {
+	fn eq(&self, other: &Symbol) -> bool {
+		ptr::eq(self.0, other.0)
+	}
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		ptr::hash(self.0, state)
+	}
+}
+
+impl Ord for Symbol {
+	fn cmp(&self, other: &Symbol) -> Ordering {
+		if self == other {
+			Ordering::Equal
+		} else {
+			self.0.cmp(other.0)
+		}
+	}
+}
+
+impl PartialOrd for Symbol {
+	fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for Symbol {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+impl fmt::Debug for Symbol {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn names_are_interned_once() {
+		let a = Symbol::intern("interned_once");
+		let b = Symbol::intern(&String::from("interned_once"));
+		assert_eq!(a, b);
+		assert!(ptr::eq(a.as_str(), b.as_str()));
+		assert!(a != Symbol::intern("interned_twice"));
+		assert_eq!(a.to_string(), "interned_once");
+	}
+
+	#[test]
+	fn symbols_order_by_name_not_by_interning() {
+		let late = Symbol::intern("ordered_b");
+		let early = Symbol::intern("ordered_a");
+		assert!(early < late);
+		assert_eq!(late.cmp(&Symbol::intern("ordered_b")), Ordering::Equal);
+		let mut symbols = [Symbol::intern("x10"), Symbol::intern("x1"), Symbol::intern("x_")];
+		symbols.sort();
+		assert_eq!(symbols.iter().map(Symbol::as_str).collect::<Vec<_>>(), vec!["x1", "x10", "x_"]);
+	}
+
+	#[test]
+	fn valid_names() {
+		for name in &["p", "door_open2", "x_"] {
+			assert!(Symbol::is_valid_name(name), "{}", name);
+		}
+		for name in &["", "P", "2p", "_p", "p-q", "pQ"] {
+			assert!(!Symbol::is_valid_name(name), "{}", name);
+		}
+	}
+}
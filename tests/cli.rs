@@ -0,0 +1,192 @@
+extern crate sequents;
+
+use sequents::json::Json;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs the validator with the given arguments and standard input,
+// giving its exit status, standard output and standard error.
+fn run(args: &[&str], input: &str) -> (i32, String, String) {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_sequents"))
+	.args(args)
+	// keeps the named formulae of the repl to the test, away from those of the user's sessions
+	.env("SEQUENTS_DEFINITIONS", "")
+	.stdin(Stdio::piped())
+	.stdout(Stdio::piped())
+	.stderr(Stdio::piped())
+	.spawn()
+	.unwrap();
+	child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+	let out = child.wait_with_output().unwrap();
+	(out.status.code().unwrap(), String::from_utf8(out.stdout).unwrap(), String::from_utf8(out.stderr).unwrap())
+}
+
+// The lines in the order they were printed, without their timings, which vary from run to run.
+fn untimed(out: &str) -> Vec<String> {
+	out.lines().map(|x| match (x.find(" rules, "), x.find(" ms)")) {
+		(Some(a), Some(b)) => format!("{} rules{}", &x[..a], &x[b+3..]),
+		_ => x.to_owned(),
+	}).collect()
+}
+
+const BATCH: &str = "\
+	p -> p # expect: valid\n\
+	[]p -> p\n\
+	<>p -> []p # expect: valid\n\
+	\n\
+	p & # expect: invalid\n\
+	q # expect: maybe\n\
+";
+
+#[test]
+fn batch_reports_every_line_and_sums_them_up() {
+	let (status, out, err) = run(&["--batch", "-"], BATCH);
+	assert_eq!(untimed(&out), vec![
+		"line 1: valid (1 rules): p->p",
+		"line 2: invalid (1 rules): []p->p",
+		"line 3: invalid, expected valid (2 rules): <>p->[]p",
+		"line 5: parse error at column 5: expected operand after ∧",
+		"line 6: unknown expectation 'maybe', expected valid or invalid",
+		"1 valid, 2 invalid, 2 errors, 0 timed out, 1 expectations not met",
+	]);
+	assert_eq!((status, err.as_str()), (2, ""));
+}
+
+#[test]
+fn quiet_batches_only_report_the_lines_that_failed() {
+	let (status, out, err) = run(&["--batch", "-", "--quiet"], BATCH);
+	assert_eq!((status, out.as_str()), (2, ""));
+	assert_eq!(untimed(&err), vec![
+		"line 3: invalid, expected valid (2 rules): <>p->[]p",
+		"line 5: parse error at column 5: expected operand after ∧",
+		"line 6: unknown expectation 'maybe', expected valid or invalid",
+	]);
+	assert_eq!(run(&["--batch", "-", "--quiet"], "p -> p # expect: valid\n[]p -> p"), (0, String::new(), String::new()));
+}
+
+#[test]
+fn json_batches_list_the_lines_that_failed_even_when_quiet() {
+	let lines = |out: &str| {
+		let json = Json::parse(out).unwrap();
+		let results = match json.get("results") {
+			Some(Json::Array(ref v)) => v.iter().map(|x| x.get("line").and_then(Json::as_u32).unwrap()).collect::<Vec<_>>(),
+			_ => panic!("no results in {}", out),
+		};
+		let counts = ["valid", "invalid", "errors", "timeouts", "violations"].iter()
+		.map(|x| json.get(x).and_then(Json::as_u32).unwrap())
+		.collect::<Vec<_>>();
+		(results, counts)
+	};
+	let (status, out, err) = run(&["--batch", "-", "--format", "json"], BATCH);
+	assert_eq!((status, err.as_str()), (2, ""));
+	assert_eq!(lines(&out), (vec![1, 2, 3, 5, 6], vec![1, 2, 2, 0, 1]));
+	let (status, out, err) = run(&["--batch", "-", "--format", "json", "--quiet"], BATCH);
+	assert_eq!((status, err.as_str()), (2, ""));
+	assert_eq!(lines(&out), (vec![3, 5, 6], vec![1, 2, 2, 0, 1]));
+	assert_eq!(run(&["--batch", "-", "--format", "json", "--quiet"], "p -> p"), (0, String::new(), String::new()));
+}
+
+#[test]
+fn batches_exit_with_the_worst_outcome() {
+	assert_eq!(run(&["--batch", "-", "--quiet"], "[]p -> p # expect: invalid").0, 0);
+	assert_eq!(run(&["--batch", "-", "--quiet"], "[]p -> p # expect: valid").0, 1);
+	assert_eq!(run(&["--batch", "-", "--quiet", "--logic", "T"], "[]p -> p # expect: valid").0, 0);
+	assert_eq!(run(&["--batch", "-", "--quiet"], "[]p -> p # expect: valid\n(p").0, 2);
+}
+
+#[test]
+fn the_exit_status_tells_the_outcome() {
+	assert_eq!(run(&["--quiet", "[]p -> [][]p", "--logic", "S4"], ""), (0, String::new(), String::new()));
+	assert_eq!(run(&["--quiet", "[]p -> [][]p"], ""), (1, String::new(), String::new()));
+	assert_eq!(run(&["check", "--quiet", "--model", "-", "p"], "").0, 2);
+	let (status, out, err) = run(&["--quiet", "p &"], "");
+	assert_eq!((status, out.as_str()), (2, ""));
+	assert!(err.starts_with("Failed to parse the formula: expected operand after ∧\n"), "{}", err);
+}
+
+#[test]
+fn errors_go_to_standard_error_unless_the_format_is_json() {
+	for quiet in &[vec![], vec!["--quiet"]] {
+		let (status, out, err) = run(&[&["p &"][..], quiet].concat(), "");
+		assert_eq!((status, out.as_str()), (2, ""));
+		assert!(err.starts_with("Failed to parse the formula"), "{}", err);
+		let (status, out, err) = run(&[&["p &", "--format", "json"][..], quiet].concat(), "");
+		assert_eq!((status, err.as_str()), (2, ""));
+		assert_eq!(Json::parse(&out).unwrap().get("span"), Some(&Json::Array(vec![Json::Number(3.0), Json::Number(3.0)])));
+	}
+	let (status, out, err) = run(&["--logic", "S6", "p"], "");
+	assert_eq!((status, out.as_str()), (2, ""));
+	assert!(err.starts_with("unknown logic 'S6'"), "{}", err);
+}
+
+#[test]
+fn proofs_give_up_at_the_timeout() {
+	// far too short for the proof to be done in
+	let (status, out, err) = run(&["(p -> q) -> (q -> r) -> (p -> r)", "--timeout", "1e-9"], "");
+	assert_eq!((status, out.as_str(), err.as_str()), (3, "", "Gave up, as the proof was not done by its deadline\n"));
+	let (status, out, _) = run(&["(p -> q) -> (q -> r) -> (p -> r)", "--timeout", "1e-9", "--format", "json"], "");
+	assert_eq!(status, 3);
+	assert!(Json::parse(&out).unwrap().get("error").is_some(), "{}", out);
+	let (status, out, _) = run(&["--batch", "-", "--timeout", "1e-9"], "p -> q -> p\np & q -> p # expect: valid");
+	assert_eq!(status, 3);
+	assert!(out.contains("line 2: gave up, as the proof was not done by its deadline: p&q->p\n"), "{}", out);
+	assert!(out.ends_with("0 valid, 0 invalid, 0 errors, 2 timed out, 0 expectations not met\n"), "{}", out);
+	// but a limit too long to reach is none at all
+	assert_eq!(run(&["--quiet", "p -> p", "--timeout", "1e30"], "").0, 0);
+	for bad in &["0", "-1", "inf", "soon"] {
+		let (status, _, err) = run(&["p", "--timeout", bad], "");
+		assert_eq!((status, err), (2, format!("--timeout needs a positive number of seconds, found '{}'\n", bad)));
+	}
+}
+
+#[test]
+fn help_and_version_need_nothing_else() {
+	let (status, out, _) = run(&["--help"], "");
+	assert_eq!(status, 0);
+	assert!(out.starts_with("Proves formulae of propositional modal logic valid"), "{}", out);
+	let (status, out, _) = run(&["repl", "--version"], "");
+	assert_eq!((status, out), (0, format!("sequents {}\n", env!("CARGO_PKG_VERSION"))));
+	let (status, out, err) = run(&["--frobnicate", "p"], "");
+	assert_eq!((status, out.as_str()), (2, ""));
+	assert!(err.starts_with("unknown option '--frobnicate'"), "{}", err);
+}
+
+#[test]
+fn repl_reads_piped_input_without_prompts_or_escapes() {
+	let input = ":let k = []p -> p\n:proof off\n:model off\nk\n:logic T\n!4\n!!\n:load /nonexistent\n:quit\np\n";
+	let (status, out, _) = run(&["repl"], input);
+	assert_eq!(status, 0);
+	assert_eq!(out.lines().take(13).collect::<Vec<_>>(), vec![
+		"proofs off",
+		"counter-models off",
+		"Given: []p->p",
+		"INVALID!",
+		"T, the logic of serial, reflexive frames",
+		"k",
+		"Logic: T",
+		"Given: []p->p",
+		"VALID!",
+		"k",
+		"Logic: T",
+		"Given: []p->p",
+		"VALID!",
+	]);
+	assert!(out.ends_with("\n") && out.lines().last().unwrap().starts_with("Failed to read /nonexistent: "), "{}", out);
+}
+
+#[test]
+fn global_premises_hold_everywhere_and_premises_only_here() {
+	assert_eq!(run(&["--quiet", "--global-premise", "p -> []p", "p -> [][]p"], "").0, 0);
+	assert_eq!(run(&["--quiet", "--premise", "p -> []p", "p -> [][]p"], "").0, 1);
+	let (status, out, _) = run(&["--format", "json", "--no-proof", "--premise", "p", "[]p"], "");
+	assert_eq!(status, 1);
+	let m = sequents::Model::from_json(Json::parse(&out).unwrap().get("counter_model").unwrap()).unwrap();
+	let p = sequents::parse("p").unwrap();
+	assert!(m.satisfies(1, &p) && !m.satisfies_everywhere(&p));
+	let (status, out, _) = run(&["--format", "json", "--no-proof", "--global-premise", "p", "[]q"], "");
+	assert_eq!(status, 1);
+	let json = Json::parse(&out).unwrap();
+	assert_eq!(json.get("global_premises"), Some(&Json::Array(vec![Json::String("p".to_owned())])));
+	let m = sequents::Model::from_json(json.get("counter_model").unwrap()).unwrap();
+	assert!(m.satisfies_everywhere(&p) && !m.satisfies(1, &sequents::parse("[]q").unwrap()));
+}